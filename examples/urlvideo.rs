@@ -60,9 +60,9 @@ impl Application for GstreamerIcedProgram {
             PlayStatus::Stop | PlayStatus::End => button(text("|>")).on_press(
                 GStreamerIcedMessage::Gst(GStreamerMessage::PlayStatusChanged(PlayStatus::Playing)),
             ),
-            PlayStatus::Playing => button(text("[]")).on_press(GStreamerIcedMessage::Gst(
-                GStreamerMessage::PlayStatusChanged(PlayStatus::Stop),
-            )),
+            PlayStatus::Playing | PlayStatus::Buffering => button(text("[]")).on_press(
+                GStreamerIcedMessage::Gst(GStreamerMessage::PlayStatusChanged(PlayStatus::Stop)),
+            ),
         }
         .into();
         let video = Image::new(frame).width(Length::Fill);
@@ -0,0 +1,514 @@
+use futures::channel::mpsc;
+use gst::prelude::*;
+use gstreamer as gst;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use super::{set_number_property, GStreamerMessage, GstreamerIcedBase, IcedGStreamerError};
+
+// the manifest is small, stop reading it if something else comes
+const MAX_MANIFEST_SIZE: usize = 4 * 1024 * 1024;
+
+const ADAPTIVE_DEMUXERS: [&str; 4] = ["hlsdemux", "dashdemux", "hlsdemux2", "dashdemux2"];
+
+/// a variant of a HLS or DASH stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamVariant {
+    /// bits per second
+    pub bitrate: u64,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+/// how the variant of a HLS or DASH stream is chosen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VariantSelection {
+    /// let the demuxer choose by the network speed
+    #[default]
+    Auto,
+    /// always play the variant of the index in [GstreamerIcedBase::variants]
+    Pinned(usize),
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct AdaptiveSettings {
+    selection: VariantSelection,
+    max_bitrate: Option<u64>,
+}
+
+// one lock for all of it, it is changed from the streaming threads and the ui thread
+#[derive(Debug, Default)]
+struct AdaptiveState {
+    demux: Option<gst::Element>,
+    variants: Vec<StreamVariant>,
+    settings: AdaptiveSettings,
+}
+
+impl AdaptiveState {
+    // the properties are set after the lock is released, setting them may take the locks of
+    // the demuxer
+    fn to_apply(&self) -> Option<(gst::Element, Vec<StreamVariant>, AdaptiveSettings)> {
+        let demux = self.demux.clone()?;
+        Some((demux, self.variants.clone(), self.settings))
+    }
+}
+
+/// watch the HLS or DASH demuxer created by playbin, read the variants from the manifest going
+/// into it and apply the settings to it
+#[derive(Debug, Default)]
+pub(crate) struct AdaptiveStreaming {
+    state: Arc<Mutex<AdaptiveState>>,
+}
+
+impl AdaptiveStreaming {
+    /// `video_pad` is the sink pad of the video output, the variant switch is detected by the
+    /// resolution of the video going into it
+    pub(crate) fn watch(
+        playbin: &gst::Bin,
+        video_pad: &gst::Pad,
+        sd: mpsc::Sender<GStreamerMessage>,
+    ) -> Self {
+        let adaptive = Self::default();
+
+        let state_ref = Arc::clone(&adaptive.state);
+        let found_sd = sd.clone();
+        playbin.connect_deep_element_added(move |_, _, element| {
+            let is_demux = element
+                .factory()
+                .map(|factory| ADAPTIVE_DEMUXERS.contains(&factory.name().as_str()))
+                .unwrap_or(false);
+            if !is_demux {
+                return;
+            }
+            let settings = match state_ref.lock() {
+                Ok(mut state) => {
+                    state.demux = Some(element.clone());
+                    state.to_apply()
+                }
+                Err(_) => None,
+            };
+            if let Some((demux, variants, settings)) = settings {
+                apply_settings(&demux, &variants, settings);
+            }
+            watch_manifest(element, &state_ref, found_sd.clone());
+        });
+
+        let state_ref = Arc::clone(&adaptive.state);
+        let current = Arc::new(AtomicUsize::new(usize::MAX));
+        video_pad.connect_notify(Some("caps"), move |pad, _| {
+            let Some(caps) = pad.current_caps() else {
+                return;
+            };
+            let Some(s) = caps.structure(0) else {
+                return;
+            };
+            let (Ok(width), Ok(height)) = (s.get::<i32>("width"), s.get::<i32>("height")) else {
+                return;
+            };
+            let Ok(state) = state_ref.lock() else {
+                return;
+            };
+            let Some(index) = state.variants.iter().position(|variant| {
+                variant.width == Some(width as u32) && variant.height == Some(height as u32)
+            }) else {
+                return;
+            };
+            drop(state);
+            if current.swap(index, Ordering::Relaxed) != index {
+                sd.clone()
+                    .try_send(GStreamerMessage::VariantSwitched(index))
+                    .ok();
+            }
+        });
+
+        adaptive
+    }
+}
+
+// collect the manifest until its end, then read the variants from it
+fn watch_manifest(
+    demux: &gst::Element,
+    state: &Arc<Mutex<AdaptiveState>>,
+    sd: mpsc::Sender<GStreamerMessage>,
+) {
+    let Some(sinkpad) = demux.static_pad("sink") else {
+        return;
+    };
+    let manifest = Mutex::new(Vec::new());
+    let state = Arc::clone(state);
+    sinkpad.add_probe(
+        gst::PadProbeType::BUFFER | gst::PadProbeType::EVENT_DOWNSTREAM,
+        move |_, info| {
+            let Ok(mut manifest) = manifest.lock() else {
+                return gst::PadProbeReturn::Remove;
+            };
+            if let Some(buffer) = info.buffer() {
+                if let Ok(map) = buffer.map_readable() {
+                    manifest.extend_from_slice(map.as_slice());
+                }
+                if manifest.len() > MAX_MANIFEST_SIZE {
+                    return gst::PadProbeReturn::Remove;
+                }
+                return gst::PadProbeReturn::Ok;
+            }
+            match info.event() {
+                Some(event) if event.type_() == gst::EventType::Eos => {}
+                _ => return gst::PadProbeReturn::Ok,
+            }
+            let text = String::from_utf8_lossy(&manifest);
+            let found = if text.trim_start().starts_with("#EXTM3U") {
+                parse_hls(&text)
+            } else {
+                parse_dash(&text)
+            };
+            drop(manifest);
+            let settings = match state.lock() {
+                Ok(mut state) => {
+                    state.variants = found;
+                    state.to_apply()
+                }
+                Err(_) => None,
+            };
+            if let Some((demux, variants, settings)) = settings {
+                apply_settings(&demux, &variants, settings);
+            }
+            sd.clone().try_send(GStreamerMessage::VariantsFound).ok();
+            gst::PadProbeReturn::Remove
+        },
+    );
+}
+
+// the attributes are split by commas, but the quoted values may contain commas
+fn hls_attributes(line: &str) -> Vec<(&str, &str)> {
+    let mut attributes = Vec::new();
+    let mut quoted = false;
+    let mut start = 0;
+    for (index, c) in line.char_indices().chain([(line.len(), ',')]) {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                if let Some((key, value)) = line[start..index].split_once('=') {
+                    attributes.push((key.trim(), value.trim().trim_matches('"')));
+                }
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    attributes
+}
+
+fn parse_hls(text: &str) -> Vec<StreamVariant> {
+    text.lines()
+        .filter_map(|line| line.trim().strip_prefix("#EXT-X-STREAM-INF:"))
+        .filter_map(|line| {
+            let mut variant = StreamVariant {
+                bitrate: 0,
+                width: None,
+                height: None,
+            };
+            for (key, value) in hls_attributes(line) {
+                match key {
+                    "BANDWIDTH" => variant.bitrate = value.parse().ok()?,
+                    "RESOLUTION" => {
+                        if let Some((width, height)) = value.split_once('x') {
+                            variant.width = width.parse().ok();
+                            variant.height = height.parse().ok();
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Some(variant)
+        })
+        .collect()
+}
+
+// the attributes may be separated by any whitespace, and the name must not be the end of
+// another one, like width of maxWidth
+fn xml_attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let pattern = format!("{name}=\"");
+    let mut offset = 0;
+    while let Some(found) = tag[offset..].find(&pattern) {
+        let index = offset + found;
+        let start = index + pattern.len();
+        if tag[..index].ends_with(char::is_whitespace) {
+            let end = tag[start..].find('"')? + start;
+            return Some(&tag[start..end]);
+        }
+        offset = start;
+    }
+    None
+}
+
+fn is_audio(tag: &str) -> bool {
+    xml_attribute(tag, "contentType") == Some("audio")
+        || xml_attribute(tag, "mimeType")
+            .map(|mime| mime.starts_with("audio/"))
+            .unwrap_or(false)
+}
+
+fn parse_dash(text: &str) -> Vec<StreamVariant> {
+    // the size and the type are often given once by the adaptation set for all of its
+    // representations
+    let mut variants: Vec<(StreamVariant, bool)> = Vec::new();
+    for set in text.split("<AdaptationSet").skip(1) {
+        let Some(end) = set.find('>') else {
+            continue;
+        };
+        let set_tag = &set[..end];
+        let set_audio = is_audio(set_tag);
+        for tag in set.split("<Representation").skip(1) {
+            let Some(end) = tag.find('>') else {
+                continue;
+            };
+            let tag = &tag[..end];
+            let Some(bitrate) = xml_attribute(tag, "bandwidth").and_then(|b| b.parse().ok()) else {
+                continue;
+            };
+            let size = |name: &str| {
+                xml_attribute(tag, name)
+                    .or_else(|| xml_attribute(set_tag, name))
+                    .and_then(|value| value.parse().ok())
+            };
+            let variant = StreamVariant {
+                bitrate,
+                width: size("width"),
+                height: size("height"),
+            };
+            let audio = set_audio || is_audio(tag);
+            variants.push((variant, audio && variant.width.is_none()));
+        }
+    }
+    // the audio representations are not variants to choose when there is video
+    let has_video = variants.iter().any(|(_, audio)| !audio);
+    variants
+        .into_iter()
+        .filter(|(_, audio)| !has_video || !audio)
+        .map(|(variant, _)| variant)
+        .collect()
+}
+
+fn apply_settings(demux: &gst::Element, variants: &[StreamVariant], settings: AdaptiveSettings) {
+    // the properties are different between adaptivedemux and adaptivedemux2, so set the ones
+    // exist
+    let has_max_bitrate =
+        set_number_property(demux, "max-bitrate", settings.max_bitrate.unwrap_or(0));
+    let pinned = match settings.selection {
+        VariantSelection::Pinned(index) => variants.get(index).map(|variant| variant.bitrate),
+        VariantSelection::Auto => None,
+    };
+    // without max-bitrate, the cap can only be done by fixing the speed
+    let speed = pinned.or(settings.max_bitrate.filter(|_| !has_max_bitrate));
+
+    // the demuxer chooses the best variant under speed * ratio
+    let ratio = if speed.is_some() { 1.0 } else { 0.8 };
+    if let Some(pspec) = demux
+        .find_property("bandwidth-target-ratio")
+        .or_else(|| demux.find_property("bitrate-limit"))
+    {
+        demux.set_property(pspec.name(), ratio as f32);
+    }
+    if !set_number_property(demux, "connection-bitrate", speed.unwrap_or(0)) {
+        set_number_property(
+            demux,
+            "connection-speed",
+            speed.map(|speed| speed.div_ceil(1000)).unwrap_or(0),
+        );
+    }
+}
+
+impl GstreamerIcedBase {
+    /// the variants of the HLS or DASH stream, they are known after
+    /// [GStreamerMessage::VariantsFound]
+    pub fn variants(&self) -> Vec<StreamVariant> {
+        self.adaptive
+            .state
+            .lock()
+            .map(|state| state.variants.clone())
+            .unwrap_or_default()
+    }
+
+    /// the index of the variant playing now, detected by the resolution of the video
+    pub fn current_variant(&self) -> Option<usize> {
+        self.current_variant
+    }
+
+    /// the current [VariantSelection]
+    pub fn variant_selection(&self) -> VariantSelection {
+        self.adaptive
+            .state
+            .lock()
+            .map(|state| state.settings.selection)
+            .unwrap_or_default()
+    }
+
+    /// pin a variant or let the demuxer choose, it can be changed while playing
+    pub fn set_variant_selection(
+        &mut self,
+        selection: VariantSelection,
+    ) -> Result<(), IcedGStreamerError> {
+        self.update_adaptive_settings(|state| {
+            if let VariantSelection::Pinned(index) = selection {
+                if index >= state.variants.len() {
+                    return Err(IcedGStreamerError::InvalidVariant(index));
+                }
+            }
+            state.settings.selection = selection;
+            Ok(())
+        })
+    }
+
+    /// the max bitrate in bits per second
+    pub fn max_bitrate(&self) -> Option<u64> {
+        self.adaptive
+            .state
+            .lock()
+            .map(|state| state.settings.max_bitrate)
+            .unwrap_or_default()
+    }
+
+    /// never choose the variants over the bitrate in bits per second, when the demuxer cannot
+    /// cap it, the best variant under it is pinned
+    pub fn set_max_bitrate(&mut self, max_bitrate: Option<u64>) -> Result<(), IcedGStreamerError> {
+        self.update_adaptive_settings(|state| {
+            state.settings.max_bitrate = max_bitrate;
+            Ok(())
+        })
+    }
+
+    fn update_adaptive_settings<F>(&mut self, f: F) -> Result<(), IcedGStreamerError>
+    where
+        F: FnOnce(&mut AdaptiveState) -> Result<(), IcedGStreamerError>,
+    {
+        let settings = {
+            let mut state = self
+                .adaptive
+                .state
+                .lock()
+                .map_err(|_| IcedGStreamerError::Poisoned)?;
+            f(&mut state)?;
+            state.to_apply()
+        };
+        if let Some((demux, variants, settings)) = settings {
+            apply_settings(&demux, &variants, settings);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hls_codecs_with_quoted_commas() {
+        let playlist = "#EXTM3U\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=1280000,CODECS=\"avc1.4d401f,mp4a.40.2\",RESOLUTION=1280x720\n\
+            720p.m3u8\n";
+        assert_eq!(
+            parse_hls(playlist),
+            vec![StreamVariant {
+                bitrate: 1280000,
+                width: Some(1280),
+                height: Some(720),
+            }]
+        );
+    }
+
+    #[test]
+    fn hls_without_resolution() {
+        let playlist = "#EXTM3U\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=64000,CODECS=\"mp4a.40.2\"\n\
+            audio.m3u8\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=2560000,RESOLUTION=1920x1080\n\
+            1080p.m3u8\n";
+        assert_eq!(
+            parse_hls(playlist),
+            vec![
+                StreamVariant {
+                    bitrate: 64000,
+                    width: None,
+                    height: None,
+                },
+                StreamVariant {
+                    bitrate: 2560000,
+                    width: Some(1920),
+                    height: Some(1080),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn hls_attributes_keep_quoted_commas() {
+        assert_eq!(
+            hls_attributes("BANDWIDTH=1,CODECS=\"a,b\",NAME=\"x\""),
+            vec![("BANDWIDTH", "1"), ("CODECS", "a,b"), ("NAME", "x")]
+        );
+    }
+
+    #[test]
+    fn dash_size_on_the_adaptation_set() {
+        let manifest = r#"<MPD>
+  <Period>
+    <AdaptationSet mimeType="video/mp4" width="1280" height="720">
+      <Representation id="1" bandwidth="1000000"/>
+      <Representation id="2" bandwidth="2000000"/>
+    </AdaptationSet>
+    <AdaptationSet mimeType="audio/mp4">
+      <Representation id="3" bandwidth="128000"/>
+    </AdaptationSet>
+  </Period>
+</MPD>"#;
+        assert_eq!(
+            parse_dash(manifest),
+            vec![
+                StreamVariant {
+                    bitrate: 1000000,
+                    width: Some(1280),
+                    height: Some(720),
+                },
+                StreamVariant {
+                    bitrate: 2000000,
+                    width: Some(1280),
+                    height: Some(720),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn dash_size_on_the_representation() {
+        let manifest = r#"<MPD><Period>
+<AdaptationSet contentType="video" maxWidth="1920">
+<Representation
+  id="1" bandwidth="500000" width="640"
+  height="360"/>
+</AdaptationSet>
+</Period></MPD>"#;
+        assert_eq!(
+            parse_dash(manifest),
+            vec![StreamVariant {
+                bitrate: 500000,
+                width: Some(640),
+                height: Some(360),
+            }]
+        );
+    }
+
+    #[test]
+    fn dash_audio_only() {
+        let manifest = r#"<MPD><Period>
+<AdaptationSet mimeType="audio/mp4">
+<Representation id="1" bandwidth="64000"/>
+<Representation id="2" bandwidth="128000"/>
+</AdaptationSet>
+</Period></MPD>"#;
+        let bitrates: Vec<u64> = parse_dash(manifest)
+            .iter()
+            .map(|variant| variant.bitrate)
+            .collect();
+        assert_eq!(bitrates, vec![64000, 128000]);
+    }
+}
@@ -0,0 +1,265 @@
+use gst::prelude::*;
+use gstreamer as gst;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use super::plugins::make_element;
+use super::{static_pad, GstreamerIcedBase, IcedGStreamerError};
+
+/// an audio output device, you can get them from [audio_output_devices]
+#[derive(Debug, Clone)]
+pub struct AudioOutputDevice {
+    device: gst::Device,
+}
+
+impl AudioOutputDevice {
+    /// the name can be shown to the user
+    pub fn display_name(&self) -> String {
+        self.device.display_name().to_string()
+    }
+
+    /// the device class, like `Audio/Sink`
+    pub fn device_class(&self) -> String {
+        self.device.device_class().to_string()
+    }
+
+    /// the sound server the device belongs to, like pulseaudio, pipewire or alsa
+    pub fn api(&self) -> Option<String> {
+        self.device
+            .properties()
+            .and_then(|properties| properties.get::<String>("device.api").ok())
+    }
+}
+
+/// where the audio of [GstreamerIcedBase] goes
+#[derive(Debug, Clone, Default)]
+pub enum AudioOutput {
+    /// let gstreamer choose the device
+    #[default]
+    Auto,
+    /// a device from [audio_output_devices]
+    Device(AudioOutputDevice),
+    /// drop the audio but keep the timing, useful for headless test
+    Fake,
+}
+
+impl AudioOutput {
+    fn make_sink(&self) -> Result<gst::Element, IcedGStreamerError> {
+        Ok(match self {
+            AudioOutput::Auto => make_element("autoaudiosink")?.build()?,
+            AudioOutput::Device(output) => output.device.create_element(None)?,
+            AudioOutput::Fake => make_element("fakesink")?.property("sync", true).build()?,
+        })
+    }
+}
+
+/// the center frequencies of the bands of the equalizer in Hz
+pub const EQUALIZER_BANDS: [u32; 10] = [29, 59, 119, 237, 474, 947, 1889, 3770, 7523, 15011];
+
+/// the min gain of an equalizer band in dB
+pub const EQUALIZER_MIN_GAIN: f64 = -24.0;
+
+/// the max gain of an equalizer band in dB
+pub const EQUALIZER_MAX_GAIN: f64 = 12.0;
+
+/// how the loudness is normalised by the ReplayGain tags
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReplayGain {
+    /// ignore the ReplayGain tags
+    #[default]
+    Off,
+    /// normalise every track on its own
+    Track,
+    /// keep the loudness difference between the tracks of an album
+    Album,
+}
+
+/// the `audio-filter` of playbin: audioconvert ! rgvolume ! equalizer-10bands ! audioconvert !
+/// audiopanorama
+#[derive(Debug)]
+pub(crate) struct AudioFilter {
+    equalizer: gst::Element,
+    rgvolume: gst::Element,
+    panorama: gst::Element,
+    replay_gain_enabled: Arc<AtomicBool>,
+}
+
+impl AudioFilter {
+    /// build the filter bin, it is [None] if any of the plugins is not installed
+    pub(crate) fn new() -> Result<Option<(Self, gst::Bin)>, IcedGStreamerError> {
+        let plugins_found = ["rgvolume", "equalizer-10bands", "audiopanorama"]
+            .iter()
+            .all(|name| gst::ElementFactory::find(name).is_some());
+        if !plugins_found {
+            return Ok(None);
+        }
+
+        let filter_bin = gst::Bin::new();
+        let audioconvert = make_element("audioconvert")?.build()?;
+        let rgvolume = make_element("rgvolume")?.build()?;
+        let equalizer = make_element("equalizer-10bands")?.build()?;
+        let panorama_convert = make_element("audioconvert")?.build()?;
+        let panorama = make_element("audiopanorama")?.build()?;
+
+        let elements = [
+            &audioconvert,
+            &rgvolume,
+            &equalizer,
+            &panorama_convert,
+            &panorama,
+        ];
+        filter_bin.add_many(elements)?;
+        gst::Element::link_many(elements)?;
+
+        let replay_gain_enabled = Arc::new(AtomicBool::new(false));
+        let enabled = Arc::clone(&replay_gain_enabled);
+        // rgvolume has no switch, so hide the tags from it when it is off
+        static_pad(&rgvolume, "sink")?.add_probe(
+            gst::PadProbeType::EVENT_DOWNSTREAM,
+            move |_, info| match info.event() {
+                Some(event)
+                    if event.type_() == gst::EventType::Tag && !enabled.load(Ordering::Relaxed) =>
+                {
+                    gst::PadProbeReturn::Drop
+                }
+                _ => gst::PadProbeReturn::Ok,
+            },
+        );
+
+        let sinkpad = static_pad(&audioconvert, "sink")?;
+        let sinkghost = gst::GhostPad::builder_with_target(&sinkpad)?.build();
+        sinkghost.set_active(true)?;
+        filter_bin.add_pad(&sinkghost)?;
+
+        let srcpad = static_pad(&panorama, "src")?;
+        let srcghost = gst::GhostPad::builder_with_target(&srcpad)?.build();
+        srcghost.set_active(true)?;
+        filter_bin.add_pad(&srcghost)?;
+
+        Ok(Some((
+            Self {
+                equalizer,
+                rgvolume,
+                panorama,
+                replay_gain_enabled,
+            },
+            filter_bin,
+        )))
+    }
+}
+
+/// list all the audio output devices
+pub fn audio_output_devices() -> Result<Vec<AudioOutputDevice>, IcedGStreamerError> {
+    gst::init()?;
+
+    let monitor = gst::DeviceMonitor::new();
+    monitor.add_filter(Some("Audio/Sink"), None);
+    monitor.start()?;
+    let devices = monitor.devices();
+    monitor.stop();
+
+    Ok(devices
+        .into_iter()
+        .map(|device| AudioOutputDevice { device })
+        .collect())
+}
+
+impl GstreamerIcedBase {
+    /// switch the audio output, it can be done while playing, the position will be kept
+    pub fn set_audio_output(&mut self, output: &AudioOutput) -> Result<(), IcedGStreamerError> {
+        let sink = output.make_sink()?;
+
+        let (_, state, _) = self.source.state(gst::ClockTime::ZERO);
+        if state <= gst::State::Ready {
+            self.source.set_property("audio-sink", &sink);
+            return Ok(());
+        }
+
+        // playbin only accept a new audio sink when it is not running
+        let position = self.source.query_position::<gst::ClockTime>();
+        self.source.set_state(gst::State::Ready)?;
+        self.source.set_property("audio-sink", &sink);
+        self.source.set_state(state)?;
+        if let Some(position) = position {
+            self.source.state(gst::ClockTime::from_seconds(5)).0?;
+            self.source
+                .seek_simple(gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE, position)?;
+        }
+        Ok(())
+    }
+
+    fn audio_filter(&self) -> Result<&AudioFilter, IcedGStreamerError> {
+        self.audio_filter
+            .as_ref()
+            .ok_or(IcedGStreamerError::AudioFilterUnavailable)
+    }
+
+    /// set the gain of one band of the equalizer in dB, the frequencies of the bands are in
+    /// [EQUALIZER_BANDS]
+    pub fn set_equalizer_band(&mut self, band: usize, gain: f64) -> Result<(), IcedGStreamerError> {
+        if band >= EQUALIZER_BANDS.len() {
+            return Err(IcedGStreamerError::EqualizerBand(band));
+        }
+        self.audio_filter()?.equalizer.set_property(
+            &format!("band{band}"),
+            gain.clamp(EQUALIZER_MIN_GAIN, EQUALIZER_MAX_GAIN),
+        );
+        Ok(())
+    }
+
+    /// set the gains of all bands of the equalizer in dB
+    pub fn set_equalizer(&mut self, gains: [f64; 10]) -> Result<(), IcedGStreamerError> {
+        for (band, gain) in gains.into_iter().enumerate() {
+            self.set_equalizer_band(band, gain)?;
+        }
+        Ok(())
+    }
+
+    /// get the gains of all bands of the equalizer in dB
+    pub fn equalizer(&self) -> Result<[f64; 10], IcedGStreamerError> {
+        let equalizer = &self.audio_filter()?.equalizer;
+        Ok(std::array::from_fn(|band| {
+            equalizer.property(&format!("band{band}"))
+        }))
+    }
+
+    /// set the loudness normalisation, [ReplayGain::Off] takes effect at once, the other modes
+    /// when the tags of the next track come
+    pub fn set_replay_gain(&mut self, mode: ReplayGain) -> Result<(), IcedGStreamerError> {
+        let audio_filter = self.audio_filter()?;
+        audio_filter
+            .rgvolume
+            .set_property("album-mode", mode == ReplayGain::Album);
+        if mode == ReplayGain::Off {
+            // rgvolume keeps the gain of the current track until the end of the stream, so give
+            // it a neutral gain, before the probe hides the tags from it
+            let mut tags = gst::TagList::new();
+            {
+                let tags = tags.make_mut();
+                tags.add::<gst::tags::TrackGain>(&0.0, gst::TagMergeMode::Replace);
+                tags.add::<gst::tags::TrackPeak>(&1.0, gst::TagMergeMode::Replace);
+                tags.add::<gst::tags::AlbumGain>(&0.0, gst::TagMergeMode::Replace);
+                tags.add::<gst::tags::AlbumPeak>(&1.0, gst::TagMergeMode::Replace);
+            }
+            static_pad(&audio_filter.rgvolume, "sink")?.send_event(gst::event::Tag::new(tags));
+        }
+        audio_filter
+            .replay_gain_enabled
+            .store(mode != ReplayGain::Off, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// set the stereo balance, -1.0 is full left and 1.0 is full right
+    pub fn set_balance(&mut self, balance: f64) -> Result<(), IcedGStreamerError> {
+        self.audio_filter()?
+            .panorama
+            .set_property("panorama", balance.clamp(-1.0, 1.0) as f32);
+        Ok(())
+    }
+
+    /// get the stereo balance
+    pub fn balance(&self) -> Result<f64, IcedGStreamerError> {
+        let balance: f32 = self.audio_filter()?.panorama.property("panorama");
+        Ok(balance as f64)
+    }
+}
@@ -0,0 +1,388 @@
+use gst::glib;
+use gst::prelude::*;
+use gstreamer as gst;
+
+use super::plugins::make_element;
+use super::{set_number_property, static_pad, IcedGStreamerError};
+
+/// the video codecs the encoders are looked up for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VideoCodec {
+    H264,
+    Vp8,
+    Vp9,
+}
+
+impl VideoCodec {
+    // the encoders gstreamer may have, the hardware ones first
+    fn candidates(&self) -> &'static [(&'static str, bool)] {
+        match self {
+            VideoCodec::H264 => &[
+                ("nvh264enc", true),
+                ("vah264enc", true),
+                ("vah264lpenc", true),
+                ("vaapih264enc", true),
+                ("qsvh264enc", true),
+                ("amfh264enc", true),
+                ("v4l2h264enc", true),
+                ("x264enc", false),
+                ("openh264enc", false),
+            ],
+            VideoCodec::Vp8 => &[
+                ("vavp8enc", true),
+                ("vaapivp8enc", true),
+                ("v4l2vp8enc", true),
+                ("vp8enc", false),
+            ],
+            VideoCodec::Vp9 => &[
+                ("vavp9enc", true),
+                ("vaapivp9enc", true),
+                ("qsvvp9enc", true),
+                ("vp9enc", false),
+            ],
+        }
+    }
+}
+
+/// the settings every encoder takes, they are mapped to the properties of the encoder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncoderSettings {
+    /// kbit per second
+    pub bitrate: u32,
+    /// the max frames between the keyframes
+    pub keyframe_interval: u32,
+    /// trade the quality for the latency, for streaming
+    pub low_latency: bool,
+    /// only used by the h264 encoders
+    pub h264: H264Settings,
+    /// only used by the vp8 and vp9 encoders
+    pub vpx: VpxSettings,
+}
+
+impl Default for EncoderSettings {
+    fn default() -> Self {
+        Self {
+            bitrate: 4000,
+            keyframe_interval: 60,
+            low_latency: false,
+            h264: H264Settings::default(),
+            vpx: VpxSettings::default(),
+        }
+    }
+}
+
+/// the h264 profiles, the old devices often only decode baseline
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum H264Profile {
+    ConstrainedBaseline,
+    Baseline,
+    Main,
+    High,
+}
+
+impl H264Profile {
+    fn caps_name(&self) -> &'static str {
+        match self {
+            H264Profile::ConstrainedBaseline => "constrained-baseline",
+            H264Profile::Baseline => "baseline",
+            H264Profile::Main => "main",
+            H264Profile::High => "high",
+        }
+    }
+}
+
+/// what x264enc is tuned for, the other encoders do not have it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum H264Tune {
+    StillImage,
+    FastDecode,
+    ZeroLatency,
+}
+
+impl H264Tune {
+    fn nick(&self) -> &'static str {
+        match self {
+            H264Tune::StillImage => "stillimage",
+            H264Tune::FastDecode => "fastdecode",
+            H264Tune::ZeroLatency => "zerolatency",
+        }
+    }
+}
+
+/// the settings of the h264 encoders
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct H264Settings {
+    /// the profile is negotiated by the caps, so the encoder is put in a bin with a capsfilter
+    /// after it, [None] lets the encoder choose
+    pub profile: Option<H264Profile>,
+    /// [None] only tunes for the latency when [EncoderSettings::low_latency] is set
+    pub tune: Option<H264Tune>,
+}
+
+/// how much time vp8enc and vp9enc take for a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VpxDeadline {
+    Best,
+    Good,
+    Realtime,
+}
+
+impl VpxDeadline {
+    // in microseconds, 0 and 1 are the special values of libvpx
+    fn micros(&self) -> i64 {
+        match self {
+            VpxDeadline::Best => 0,
+            VpxDeadline::Good => 1_000_000,
+            VpxDeadline::Realtime => 1,
+        }
+    }
+}
+
+/// the settings of the software vp8 and vp9 encoders, the hardware ones do not have them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct VpxSettings {
+    /// [None] is realtime when [EncoderSettings::low_latency] is set, else the encoder default
+    pub deadline: Option<VpxDeadline>,
+    /// from -16 to 16, the higher the faster and the worse, clamped to it
+    pub cpu_used: Option<i32>,
+}
+
+/// an encoder installed on the machine, got from [available_encoders]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncoderInfo {
+    pub factory_name: String,
+    pub codec: VideoCodec,
+    pub hardware: bool,
+    pub rank: gst::Rank,
+}
+
+impl EncoderInfo {
+    /// make the encoder with the settings
+    pub fn make(&self, settings: EncoderSettings) -> Result<gst::Element, IcedGStreamerError> {
+        let encoder = make_element(&self.factory_name)?.build()?;
+        apply_settings(&encoder, &settings);
+        match (self.codec, settings.h264.profile) {
+            (VideoCodec::H264, Some(profile)) => with_profile(encoder, profile),
+            _ => Ok(encoder),
+        }
+    }
+
+    // the hardware encoders are installed without the device sometimes, they fail to open it
+    fn is_usable(&self) -> bool {
+        if !self.hardware {
+            return true;
+        }
+        let Some(Ok(encoder)) = make_element(&self.factory_name)
+            .ok()
+            .map(|builder| builder.build())
+        else {
+            return false;
+        };
+        let usable = encoder.set_state(gst::State::Ready).is_ok();
+        encoder.set_state(gst::State::Null).ok();
+        usable
+    }
+}
+
+/// the encoders of the codec installed on the machine, the best one first, it is ordered by the
+/// rank, the hardware ones go first when the ranks are the same
+pub fn available_encoders(codec: VideoCodec) -> Result<Vec<EncoderInfo>, IcedGStreamerError> {
+    gst::init()?;
+
+    let mut encoders: Vec<EncoderInfo> = codec
+        .candidates()
+        .iter()
+        .filter_map(|(name, hardware)| {
+            let factory = gst::ElementFactory::find(name)?;
+            Some(EncoderInfo {
+                factory_name: name.to_string(),
+                codec,
+                hardware: *hardware,
+                rank: factory.rank(),
+            })
+        })
+        .collect();
+    encoders.sort_by(|a, b| b.rank.cmp(&a.rank).then(b.hardware.cmp(&a.hardware)));
+    Ok(encoders)
+}
+
+/// make the best encoder of the codec which works, the hardware ones failing to open the device
+/// are skipped, so it falls back to the software ones
+pub fn make_encoder(
+    codec: VideoCodec,
+    settings: EncoderSettings,
+) -> Result<(EncoderInfo, gst::Element), IcedGStreamerError> {
+    let info = usable_encoder(codec)?;
+    let encoder = info.make(settings)?;
+    Ok((info, encoder))
+}
+
+// the best encoder of the codec which works, for encodebin which makes the encoder by itself
+pub(crate) fn usable_encoder(codec: VideoCodec) -> Result<EncoderInfo, IcedGStreamerError> {
+    available_encoders(codec)?
+        .into_iter()
+        .find(|info| info.is_usable())
+        .ok_or(IcedGStreamerError::NoEncoder(codec))
+}
+
+// encoder ! capsfilter in a bin, the encoders pick the profile from the caps downstream
+fn with_profile(
+    encoder: gst::Element,
+    profile: H264Profile,
+) -> Result<gst::Element, IcedGStreamerError> {
+    let bin = gst::Bin::new();
+    let capsfilter = make_element("capsfilter")?
+        .property(
+            "caps",
+            gst::Caps::builder("video/x-h264")
+                .field("profile", profile.caps_name())
+                .build(),
+        )
+        .build()?;
+    bin.add_many([&encoder, &capsfilter])?;
+    encoder.link(&capsfilter)?;
+    for (element, name) in [(&encoder, "sink"), (&capsfilter, "src")] {
+        let ghost = gst::GhostPad::with_target(&static_pad(element, name)?)?;
+        ghost.set_active(true)?;
+        bin.add_pad(&ghost)?;
+    }
+    Ok(bin.upcast())
+}
+
+// set an enum or a flags property by its nick, like `low-latency`, the flags are joined by `+`,
+// return false when the element or its version does not have the property or the value
+fn set_nick_property(element: &gst::Element, name: &str, nick: &str) -> bool {
+    let Some(pspec) = element.find_property(name) else {
+        return false;
+    };
+    let value_type = pspec.value_type();
+    let known = if let Some(class) = glib::EnumClass::with_type(value_type) {
+        class.value_by_nick(nick).is_some()
+    } else if let Some(class) = glib::FlagsClass::with_type(value_type) {
+        nick.split('+')
+            .all(|nick| class.value_by_nick(nick).is_some())
+    } else {
+        false
+    };
+    if known {
+        element.set_property_from_str(name, nick);
+    }
+    known
+}
+
+fn set_bool_property(element: &gst::Element, name: &str, value: bool) {
+    if element
+        .find_property(name)
+        .map(|pspec| pspec.value_type() == glib::Type::BOOL)
+        .unwrap_or(false)
+    {
+        element.set_property(name, value);
+    }
+}
+
+// the encoders name the same setting differently and in different units, every encoder is
+// matched by name so a new one is not given a wrong unit
+fn apply_settings(encoder: &gst::Element, settings: &EncoderSettings) {
+    let kbps = settings.bitrate as u64;
+    let bps = kbps * 1000;
+    let keyframe_interval = settings.keyframe_interval as u64;
+    let name = encoder
+        .factory()
+        .map(|factory| factory.name().to_string())
+        .unwrap_or_default();
+    match name.as_str() {
+        // kbit/s
+        "x264enc" => {
+            set_number_property(encoder, "bitrate", kbps);
+            set_number_property(encoder, "key-int-max", keyframe_interval);
+            let tune = settings.h264.tune.map(|tune| tune.nick());
+            let tune = match (tune, settings.low_latency) {
+                (Some("zerolatency") | None, true) => Some("zerolatency".to_owned()),
+                (Some(tune), true) => Some(format!("{tune}+zerolatency")),
+                (tune, false) => tune.map(str::to_owned),
+            };
+            if let Some(tune) = tune {
+                set_nick_property(encoder, "tune", &tune);
+            }
+            if settings.low_latency {
+                set_nick_property(encoder, "speed-preset", "ultrafast");
+            }
+        }
+        // bit/s
+        "openh264enc" => {
+            set_number_property(encoder, "bitrate", bps);
+            set_number_property(encoder, "gop-size", keyframe_interval);
+        }
+        // bit/s
+        "vp8enc" | "vp9enc" => {
+            set_number_property(encoder, "target-bitrate", bps);
+            set_number_property(encoder, "keyframe-max-dist", keyframe_interval);
+            let deadline = settings
+                .vpx
+                .deadline
+                .or(settings.low_latency.then_some(VpxDeadline::Realtime));
+            if let Some(deadline) = deadline {
+                encoder.set_property("deadline", deadline.micros());
+            }
+            if let Some(cpu_used) = settings.vpx.cpu_used {
+                encoder.set_property("cpu-used", cpu_used.clamp(-16, 16));
+            }
+        }
+        // kbit/s
+        "nvh264enc" => {
+            set_number_property(encoder, "bitrate", kbps);
+            set_number_property(encoder, "gop-size", keyframe_interval);
+            if settings.low_latency {
+                set_bool_property(encoder, "zerolatency", true);
+                set_number_property(encoder, "bframes", 0);
+            }
+        }
+        // kbit/s
+        "vah264enc" | "vah264lpenc" | "vavp8enc" | "vavp9enc" => {
+            set_number_property(encoder, "bitrate", kbps);
+            set_number_property(encoder, "key-int-max", keyframe_interval);
+            if settings.low_latency {
+                set_number_property(encoder, "b-frames", 0);
+                // 7 is the fastest
+                set_number_property(encoder, "target-usage", 7);
+            }
+        }
+        // kbit/s
+        "vaapih264enc" | "vaapivp8enc" | "vaapivp9enc" => {
+            set_number_property(encoder, "bitrate", kbps);
+            set_number_property(encoder, "keyframe-period", keyframe_interval);
+            if settings.low_latency {
+                set_number_property(encoder, "max-bframes", 0);
+            }
+        }
+        // kbit/s
+        "qsvh264enc" | "qsvvp9enc" => {
+            set_number_property(encoder, "bitrate", kbps);
+            set_number_property(encoder, "gop-size", keyframe_interval);
+            if settings.low_latency {
+                set_number_property(encoder, "b-frames", 0);
+                set_number_property(encoder, "target-usage", 7);
+            }
+        }
+        // kbit/s
+        "amfh264enc" => {
+            set_number_property(encoder, "bitrate", kbps);
+            set_number_property(encoder, "gop-size", keyframe_interval);
+            if settings.low_latency {
+                set_nick_property(encoder, "usage", "low-latency");
+            }
+        }
+        // the v4l2 encoders take the v4l2 controls, the bitrate is in bit/s
+        "v4l2h264enc" | "v4l2vp8enc" => {
+            let gop_size = keyframe_interval.min(i32::MAX as u64) as i32;
+            let mut controls = gst::Structure::builder("controls")
+                .field("video_bitrate", bps.min(i32::MAX as u64) as i32)
+                .field("video_gop_size", gop_size);
+            if name == "v4l2h264enc" {
+                controls = controls.field("h264_i_frame_period", gop_size);
+            }
+            encoder.set_property("extra-controls", controls.build());
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,298 @@
+use gst::glib;
+use gst::prelude::*;
+use gstreamer as gst;
+
+use super::IcedGStreamerError;
+
+// the sources reading from the network, their resource errors mean the network is down
+const NETWORK_ELEMENTS: &[&str] = &[
+    "souphttpsrc",
+    "curlhttpsrc",
+    "rtspsrc",
+    "rtmpsrc",
+    "rtmp2src",
+    "srtsrc",
+    "udpsrc",
+    "tcpclientsrc",
+    "hlsdemux",
+    "hlsdemux2",
+    "dashdemux",
+    "dashdemux2",
+    "rtmpsink",
+    "rtmp2sink",
+    "srtsink",
+    "udpsink",
+];
+
+/// the gstreamer error domain an error comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorDomain {
+    /// gstreamer itself, like a failed negotiation
+    Core,
+    /// the libraries used by the elements, like a codec library
+    Library,
+    /// files, devices and the network
+    Resource,
+    /// the media data
+    Stream,
+}
+
+impl ErrorDomain {
+    fn of(error: &glib::Error) -> Option<Self> {
+        if error.kind::<gst::CoreError>().is_some() {
+            Some(ErrorDomain::Core)
+        } else if error.kind::<gst::LibraryError>().is_some() {
+            Some(ErrorDomain::Library)
+        } else if error.kind::<gst::ResourceError>().is_some() {
+            Some(ErrorDomain::Resource)
+        } else if error.kind::<gst::StreamError>().is_some() {
+            Some(ErrorDomain::Stream)
+        } else {
+            None
+        }
+    }
+}
+
+/// what went wrong, so the application can react to it, got by [IcedGStreamerError::kind]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// the file or the url does not exist
+    NotFound,
+    /// the file, the device or the server refused the access
+    PermissionDenied,
+    /// the network is down or the server is not reachable
+    Network,
+    /// the device is used by something else, or the data is pushed faster than it is played
+    Busy,
+    /// the decoder or the encoder failed on the data
+    Codec,
+    /// the media is not in a format which can be handled
+    UnsupportedFormat,
+    /// a plugin is not installed
+    MissingPlugin,
+    /// the pipeline failed to change its state
+    StateChange,
+    /// the argument given to the crate is invalid
+    InvalidArgument,
+    /// gstreamer or the crate failed on their own
+    Internal,
+}
+
+impl IcedGStreamerError {
+    // keep the element and the domain of the error posted on the bus
+    pub(crate) fn from_bus_error(message: &gst::message::Error) -> Self {
+        let element = message.src();
+        let error = message.error();
+        IcedGStreamerError::Element {
+            element: element.map(|element| element.name().to_string()),
+            factory_name: element
+                .and_then(|element| element.downcast_ref::<gst::Element>())
+                .and_then(|element| element.factory())
+                .map(|factory| factory.name().to_string()),
+            domain: ErrorDomain::of(&error),
+            debug: message.debug().map(|debug| debug.to_string()),
+            error,
+        }
+    }
+
+    /// the name of the element which failed
+    pub fn element(&self) -> Option<&str> {
+        match self {
+            IcedGStreamerError::Element { element, .. } => element.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// the factory name of the element which failed, like `souphttpsrc`
+    pub fn factory_name(&self) -> Option<&str> {
+        match self {
+            IcedGStreamerError::Element { factory_name, .. } => factory_name.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// the gstreamer error domain, [None] if the error does not come from gstreamer
+    pub fn domain(&self) -> Option<ErrorDomain> {
+        match self {
+            IcedGStreamerError::Element { domain, .. } => *domain,
+            IcedGStreamerError::Glib(error) => ErrorDomain::of(error),
+            _ => None,
+        }
+    }
+
+    /// what went wrong
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            IcedGStreamerError::Element {
+                error,
+                factory_name,
+                ..
+            } => glib_error_kind(error, factory_name.as_deref()),
+            IcedGStreamerError::Glib(error) => glib_error_kind(error, None),
+            IcedGStreamerError::Io(error) => match error.kind() {
+                std::io::ErrorKind::NotFound => ErrorKind::NotFound,
+                std::io::ErrorKind::PermissionDenied => ErrorKind::PermissionDenied,
+                _ => ErrorKind::Internal,
+            },
+            IcedGStreamerError::StateChange(_) => ErrorKind::StateChange,
+            IcedGStreamerError::QueueFull(_) => ErrorKind::Busy,
+            IcedGStreamerError::Caps
+            | IcedGStreamerError::Link {
+                error: gst::PadLinkError::Noformat,
+                ..
+            } => ErrorKind::UnsupportedFormat,
+            // the source or its demuxer cannot seek, like a file without an index
+            IcedGStreamerError::SeekFailed(_) => ErrorKind::UnsupportedFormat,
+            IcedGStreamerError::Uri
+            | IcedGStreamerError::EqualizerBand(_)
+            | IcedGStreamerError::InvalidInput(_)
+            | IcedGStreamerError::InvalidVariant(_)
+            | IcedGStreamerError::InvalidFrame { .. }
+            | IcedGStreamerError::InvalidRange
+            | IcedGStreamerError::InvalidRect(_) => ErrorKind::InvalidArgument,
+            IcedGStreamerError::AudioFilterUnavailable
+            | IcedGStreamerError::VideoFilterUnavailable
+            | IcedGStreamerError::AudioSourceUnavailable
+            | IcedGStreamerError::NoEncoder(_)
+            | IcedGStreamerError::MissingPlugin(_) => ErrorKind::MissingPlugin,
+            IcedGStreamerError::Bool(_)
+            | IcedGStreamerError::Bus
+            | IcedGStreamerError::WrongType { .. }
+            | IcedGStreamerError::MissingElement(_)
+            | IcedGStreamerError::MissingPad { .. }
+            | IcedGStreamerError::Link { .. }
+            | IcedGStreamerError::Property(_)
+            | IcedGStreamerError::Duration
+            | IcedGStreamerError::Poisoned
+            | IcedGStreamerError::ClockStopped
+            | IcedGStreamerError::Flow(_) => ErrorKind::Internal,
+        }
+    }
+
+    // the pads are named by their elements, like `tee:src_0`
+    pub(crate) fn link(src: &gst::Pad, sink: &gst::Pad, error: gst::PadLinkError) -> Self {
+        let name = |pad: &gst::Pad| match pad.parent() {
+            Some(parent) => format!("{}:{}", parent.name(), pad.name()),
+            None => pad.name().to_string(),
+        };
+        IcedGStreamerError::Link {
+            src: name(src),
+            sink: name(sink),
+            error,
+        }
+    }
+
+    /// if trying again later may work, like when the network is down or the device is busy
+    pub fn is_recoverable(&self) -> bool {
+        matches!(self.kind(), ErrorKind::Network | ErrorKind::Busy)
+    }
+}
+
+fn glib_error_kind(error: &glib::Error, factory_name: Option<&str>) -> ErrorKind {
+    let from_network = factory_name
+        .map(|factory_name| NETWORK_ELEMENTS.contains(&factory_name))
+        .unwrap_or(false);
+    if let Some(kind) = error.kind::<gst::ResourceError>() {
+        return match kind {
+            gst::ResourceError::NotAuthorized => ErrorKind::PermissionDenied,
+            gst::ResourceError::Busy => ErrorKind::Busy,
+            // a missing page is not fixed by trying again, even from the network
+            gst::ResourceError::NotFound => ErrorKind::NotFound,
+            _ if from_network => ErrorKind::Network,
+            _ => ErrorKind::Internal,
+        };
+    }
+    if let Some(kind) = error.kind::<gst::StreamError>() {
+        return match kind {
+            gst::StreamError::CodecNotFound => ErrorKind::MissingPlugin,
+            gst::StreamError::Decode | gst::StreamError::Encode => ErrorKind::Codec,
+            gst::StreamError::TypeNotFound
+            | gst::StreamError::WrongType
+            | gst::StreamError::Demux
+            | gst::StreamError::Format
+            | gst::StreamError::NotImplemented => ErrorKind::UnsupportedFormat,
+            _ => ErrorKind::Internal,
+        };
+    }
+    if let Some(kind) = error.kind::<gst::CoreError>() {
+        return match kind {
+            gst::CoreError::MissingPlugin => ErrorKind::MissingPlugin,
+            gst::CoreError::StateChange => ErrorKind::StateChange,
+            gst::CoreError::Negotiation | gst::CoreError::Caps => ErrorKind::UnsupportedFormat,
+            _ => ErrorKind::Internal,
+        };
+    }
+    if let Some(kind) = error.kind::<gst::LibraryError>() {
+        return match kind {
+            gst::LibraryError::Encode => ErrorKind::Codec,
+            _ => ErrorKind::Internal,
+        };
+    }
+    ErrorKind::Internal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn element_error(error: glib::Error, factory_name: Option<&str>) -> IcedGStreamerError {
+        IcedGStreamerError::Element {
+            element: None,
+            factory_name: factory_name.map(str::to_owned),
+            domain: ErrorDomain::of(&error),
+            error,
+            debug: None,
+        }
+    }
+
+    #[test]
+    fn not_found_from_the_network_is_not_recoverable() {
+        let error = glib::Error::new(gst::ResourceError::NotFound, "");
+        assert_eq!(
+            glib_error_kind(&error, Some("souphttpsrc")),
+            ErrorKind::NotFound
+        );
+        assert!(!element_error(error, Some("souphttpsrc")).is_recoverable());
+    }
+
+    #[test]
+    fn read_from_the_network_is_recoverable() {
+        let error = glib::Error::new(gst::ResourceError::Read, "");
+        assert_eq!(
+            glib_error_kind(&error, Some("souphttpsrc")),
+            ErrorKind::Network
+        );
+        assert!(element_error(error, Some("souphttpsrc")).is_recoverable());
+    }
+
+    #[test]
+    fn read_from_a_file_is_not_the_network() {
+        let error = glib::Error::new(gst::ResourceError::Read, "");
+        assert_eq!(
+            glib_error_kind(&error, Some("filesrc")),
+            ErrorKind::Internal
+        );
+        assert_eq!(glib_error_kind(&error, None), ErrorKind::Internal);
+        assert!(!element_error(error, Some("filesrc")).is_recoverable());
+    }
+
+    #[test]
+    fn busy_device_is_recoverable() {
+        let error = glib::Error::new(gst::ResourceError::Busy, "");
+        assert_eq!(glib_error_kind(&error, Some("v4l2src")), ErrorKind::Busy);
+        assert!(element_error(error, Some("v4l2src")).is_recoverable());
+    }
+
+    #[test]
+    fn decode_is_codec() {
+        let error = glib::Error::new(gst::StreamError::Decode, "");
+        assert_eq!(
+            glib_error_kind(&error, Some("avdec_h264")),
+            ErrorKind::Codec
+        );
+        assert_eq!(glib_error_kind(&error, None), ErrorKind::Codec);
+        let error = element_error(error, Some("avdec_h264"));
+        assert_eq!(error.domain(), Some(ErrorDomain::Stream));
+        assert!(!error.is_recoverable());
+    }
+}
@@ -0,0 +1,743 @@
+use futures::channel::mpsc;
+use gst::prelude::*;
+use gst_pbutils::prelude::*;
+use gstreamer as gst;
+use gstreamer_pbutils as gst_pbutils;
+use iced::futures::SinkExt;
+use iced::futures::StreamExt;
+use smol::lock::Mutex as AsyncMutex;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use super::encoder::{usable_encoder, VideoCodec};
+use super::overlay::{overlay_elements, OverlayConfig};
+use super::plugins::{link_to_fakesink, make_element, MissingPlugin};
+use super::{is_video, next_id, pad_caps, static_pad, GstreamerIcedBase, IcedGStreamerError};
+
+// how often the export reports the progress
+const PROGRESS_INTERVAL: gst::ClockTime = gst::ClockTime::from_mseconds(100);
+
+/// the format [export_clip] and [crate::GstreamerIcedAppSrc::new_appsrc_export] encode to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportProfile {
+    /// h264 and aac in mp4
+    Mp4H264,
+    /// vp9 and opus in webm
+    WebmVp9,
+    /// the video as an animated gif, the audio is dropped
+    Gif,
+    /// opus in ogg, the video is dropped
+    AudioOnly,
+}
+
+impl ExportProfile {
+    /// the usual extension of the output file
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportProfile::Mp4H264 => "mp4",
+            ExportProfile::WebmVp9 => "webm",
+            ExportProfile::Gif => "gif",
+            ExportProfile::AudioOnly => "ogg",
+        }
+    }
+
+    // encodebin picks the encoder by the rank, so a broken hardware encoder would fail the
+    // export, the encoder which works is pinned by its factory name instead
+    pub(crate) fn encoding_profile(
+        &self,
+    ) -> Result<gst_pbutils::EncodingProfile, IcedGStreamerError> {
+        let video = |format: &str, codec: Option<VideoCodec>| {
+            let encoder = codec.map(usable_encoder).transpose()?;
+            let caps = gst::Caps::builder(format).build();
+            let mut builder = gst_pbutils::EncodingVideoProfile::builder(&caps).presence(0);
+            if let Some(encoder) = encoder.as_ref() {
+                builder = builder.preset_name(&encoder.factory_name);
+            }
+            Ok::<_, IcedGStreamerError>(builder.build())
+        };
+        let audio = |caps: &gst::Caps| {
+            gst_pbutils::EncodingAudioProfile::builder(caps)
+                .presence(0)
+                .build()
+        };
+        let opus = gst::Caps::builder("audio/x-opus").build();
+        Ok(match self {
+            ExportProfile::Mp4H264 => {
+                let mp4 = gst::Caps::builder("video/quicktime")
+                    .field("variant", "iso")
+                    .build();
+                let aac = gst::Caps::builder("audio/mpeg")
+                    .field("mpegversion", 4i32)
+                    .build();
+                gst_pbutils::EncodingContainerProfile::builder(&mp4)
+                    .add_profile(video("video/x-h264", Some(VideoCodec::H264))?)
+                    .add_profile(audio(&aac))
+                    .build()
+                    .upcast()
+            }
+            ExportProfile::WebmVp9 => {
+                let webm = gst::Caps::builder("video/webm").build();
+                gst_pbutils::EncodingContainerProfile::builder(&webm)
+                    .add_profile(video("video/x-vp9", Some(VideoCodec::Vp9))?)
+                    .add_profile(audio(&opus))
+                    .build()
+                    .upcast()
+            }
+            ExportProfile::Gif => video("image/gif", None)?.upcast(),
+            ExportProfile::AudioOnly => {
+                let ogg = gst::Caps::builder("application/ogg").build();
+                gst_pbutils::EncodingContainerProfile::builder(&ogg)
+                    .add_profile(audio(&opus))
+                    .build()
+                    .upcast()
+            }
+        })
+    }
+}
+
+/// the container [trim_clip] copies the streams into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemuxContainer {
+    Mp4,
+    /// takes nearly every codec
+    Matroska,
+    MpegTs,
+}
+
+impl RemuxContainer {
+    /// the usual extension of the output file
+    pub fn extension(&self) -> &'static str {
+        match self {
+            RemuxContainer::Mp4 => "mp4",
+            RemuxContainer::Matroska => "mkv",
+            RemuxContainer::MpegTs => "ts",
+        }
+    }
+
+    fn factory_name(&self) -> &'static str {
+        match self {
+            RemuxContainer::Mp4 => "mp4mux",
+            RemuxContainer::Matroska => "matroskamux",
+            RemuxContainer::MpegTs => "mpegtsmux",
+        }
+    }
+}
+
+/// the format of [export_animation]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimationFormat {
+    /// made by gifenc, or avenc_gif when it is not installed
+    Gif,
+    /// made by webpenc
+    WebP,
+}
+
+impl AnimationFormat {
+    /// the usual extension of the output file
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AnimationFormat::Gif => "gif",
+            AnimationFormat::WebP => "webp",
+        }
+    }
+
+    fn make_encoder(&self) -> Result<gst::Element, IcedGStreamerError> {
+        let encoder = match self {
+            AnimationFormat::Gif => {
+                let name = ["gifenc", "avenc_gif"]
+                    .into_iter()
+                    .find(|name| gst::ElementFactory::find(name).is_some())
+                    .unwrap_or("gifenc");
+                make_element(name)?.build()?
+            }
+            AnimationFormat::WebP => make_element("webpenc")?
+                .property("animated", true)
+                .build()?,
+        };
+        Ok(encoder)
+    }
+}
+
+/// the settings of [export_animation], the height follows the aspect ratio of the video
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnimationOptions {
+    pub format: AnimationFormat,
+    pub width: u32,
+    pub fps: u32,
+}
+
+impl Default for AnimationOptions {
+    fn default() -> Self {
+        Self {
+            format: AnimationFormat::Gif,
+            width: 480,
+            fps: 10,
+        }
+    }
+}
+
+/// the messages of [ClipExport], [ExportMessage::Finished], [ExportMessage::Cancelled] and
+/// [ExportMessage::Failed] are the last one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportMessage {
+    /// how much of the clip is exported, from 0.0 to 1.0
+    Progress(f64),
+    /// the range [trim_clip] really copied, the cut is moved to the keyframes, it comes before
+    /// [ExportMessage::Finished]
+    Trimmed {
+        start: std::time::Duration,
+        end: std::time::Duration,
+    },
+    Finished,
+    /// the output file is removed
+    Cancelled,
+    /// the error can be got by [ClipExport::take_error], the output file is removed
+    Failed,
+}
+
+/// a running export made by [export_clip] or [trim_clip], dropping it does not stop the export
+#[derive(Debug)]
+pub struct ClipExport {
+    id: usize,
+    rv: Arc<AsyncMutex<mpsc::Receiver<ExportMessage>>>,
+    cancelled: Arc<AtomicBool>,
+    outcome: Arc<Mutex<Option<ExportMessage>>>,
+    error: Arc<Mutex<Option<IcedGStreamerError>>>,
+}
+
+// where the data after the seek starts and ends, in the position of the media
+#[derive(Debug)]
+struct StreamRange {
+    first: AtomicU64,
+    last: AtomicU64,
+}
+
+impl Default for StreamRange {
+    fn default() -> Self {
+        Self {
+            first: AtomicU64::new(u64::MAX),
+            last: AtomicU64::new(0),
+        }
+    }
+}
+
+/// encode the media of the url between start and end into the output file, with the overlays
+/// drawn on the video, it runs in the background
+pub fn export_clip(
+    url: &url::Url,
+    start: std::time::Duration,
+    end: std::time::Duration,
+    profile: ExportProfile,
+    overlay: &OverlayConfig,
+    output: &Path,
+) -> Result<ClipExport, IcedGStreamerError> {
+    if end <= start {
+        return Err(IcedGStreamerError::InvalidRange);
+    }
+    gst::init()?;
+
+    let pipeline = gst::Pipeline::new();
+    let uridecodebin = make_element("uridecodebin")?
+        .property("uri", url.as_str())
+        .build()?;
+    let encodebin = make_element("encodebin")?
+        .property("profile", profile.encoding_profile()?)
+        .build()?;
+    let filesink = file_sink(output)?;
+    pipeline.add_many([&uridecodebin, &encodebin, &filesink])?;
+    encodebin.link(&filesink)?;
+
+    // the video goes through the overlays before encodebin
+    let overlays = overlay_elements(overlay)?;
+    let overlay_chain = if overlays.is_empty() {
+        None
+    } else {
+        let mut chain = vec![make_element("videoconvert")?.build()?];
+        chain.extend(overlays);
+        pipeline.add_many(&chain)?;
+        gst::Element::link_many(&chain)?;
+        Some(chain)
+    };
+
+    let range = Arc::new(StreamRange::default());
+    connect_streams(&pipeline, &uridecodebin, &range, move |pad| {
+        let caps = pad_caps(pad);
+        let request_pad = || encodebin.emit_by_name::<Option<gst::Pad>>("request-pad", &[&caps]);
+        match overlay_chain.as_ref() {
+            Some(chain) if is_video(&caps) => {
+                let sinkpad = chain.first()?.static_pad("sink")?;
+                if sinkpad.is_linked() {
+                    return None;
+                }
+                chain
+                    .last()?
+                    .static_pad("src")?
+                    .link(&request_pad()?)
+                    .ok()?;
+                Some(sinkpad)
+            }
+            _ => request_pad(),
+        }
+    });
+
+    start_export(
+        pipeline,
+        (start, end),
+        gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE,
+        range,
+        output,
+        false,
+    )
+}
+
+/// copy the streams of the url between start and end into the output file without encoding,
+/// the start is moved to the keyframe before it, the range really copied is sent by
+/// [ExportMessage::Trimmed], the overlays cannot be drawn without encoding
+pub fn trim_clip(
+    url: &url::Url,
+    start: std::time::Duration,
+    end: std::time::Duration,
+    container: RemuxContainer,
+    output: &Path,
+) -> Result<ClipExport, IcedGStreamerError> {
+    if end <= start {
+        return Err(IcedGStreamerError::InvalidRange);
+    }
+    gst::init()?;
+
+    let pipeline = gst::Pipeline::new();
+    let urisourcebin = make_element("urisourcebin")?
+        .property("uri", url.as_str())
+        .build()?;
+    let parsebin = make_element("parsebin")?.build()?;
+    let mux = make_element(container.factory_name())?.build()?;
+    let filesink = file_sink(output)?;
+    pipeline.add_many([&urisourcebin, &parsebin, &mux, &filesink])?;
+    mux.link(&filesink)?;
+
+    let parse_sink = static_pad(&parsebin, "sink")?;
+    urisourcebin.connect_pad_added(move |_, pad| {
+        if !parse_sink.is_linked() {
+            pad.link(&parse_sink).ok();
+        }
+    });
+
+    let range = Arc::new(StreamRange::default());
+    connect_streams(&pipeline, &parsebin, &range, move |pad| {
+        mux.compatible_pad(pad, None)
+    });
+
+    start_export(
+        pipeline,
+        (start, end),
+        gst::SeekFlags::FLUSH | gst::SeekFlags::KEY_UNIT | gst::SeekFlags::SNAP_BEFORE,
+        range,
+        output,
+        true,
+    )
+}
+
+/// render the video of the url between start and end into an animated image, like a preview
+/// for the chat, with the overlays drawn on it, the audio is dropped
+pub fn export_animation(
+    url: &url::Url,
+    start: std::time::Duration,
+    end: std::time::Duration,
+    options: AnimationOptions,
+    overlay: &OverlayConfig,
+    output: &Path,
+) -> Result<ClipExport, IcedGStreamerError> {
+    if end <= start {
+        return Err(IcedGStreamerError::InvalidRange);
+    }
+    gst::init()?;
+
+    let pipeline = gst::Pipeline::new();
+    let uridecodebin = make_element("uridecodebin")?
+        .property("uri", url.as_str())
+        .build()?;
+    let caps = gst::Caps::builder("video/x-raw")
+        .field("width", options.width.max(1) as i32)
+        .field("pixel-aspect-ratio", gst::Fraction::new(1, 1))
+        .field(
+            "framerate",
+            gst::Fraction::new(options.fps.max(1) as i32, 1),
+        )
+        .build();
+    // the overlays are drawn before the scale, like the player does
+    let mut elements = vec![make_element("videoconvert")?.build()?];
+    elements.extend(overlay_elements(overlay)?);
+    elements.extend([
+        make_element("videorate")?.build()?,
+        make_element("videoscale")?.build()?,
+        make_element("capsfilter")?
+            .property("caps", &caps)
+            .build()?,
+        make_element("videoconvert")?.build()?,
+        options.format.make_encoder()?,
+        file_sink(output)?,
+    ]);
+    pipeline.add(&uridecodebin)?;
+    pipeline.add_many(&elements)?;
+    gst::Element::link_many(&elements)?;
+
+    let video_sink = static_pad(&elements[0], "sink")?;
+    let range = Arc::new(StreamRange::default());
+    connect_streams(&pipeline, &uridecodebin, &range, move |pad| {
+        let caps = pad_caps(pad);
+        (is_video(&caps) && !video_sink.is_linked()).then(|| video_sink.clone())
+    });
+
+    start_export(
+        pipeline,
+        (start, end),
+        gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE,
+        range,
+        output,
+        false,
+    )
+}
+
+// the data before the seek is dropped, so do not wait for the preroll
+fn file_sink(output: &Path) -> Result<gst::Element, IcedGStreamerError> {
+    let location = output.to_string_lossy();
+    let filesink = make_element("filesink")?
+        .property("location", location.as_ref())
+        .property("async", false)
+        .build()?;
+    Ok(filesink)
+}
+
+// link the streams of the demuxer to the pads given by `sink_pad`, the streams without a pad
+// go to fakesink, like the audio of a gif
+fn connect_streams<F>(
+    pipeline: &gst::Pipeline,
+    demuxer: &gst::Element,
+    range: &Arc<StreamRange>,
+    sink_pad: F,
+) where
+    F: Fn(&gst::Pad) -> Option<gst::Pad> + Send + Sync + 'static,
+{
+    let pad_range = Arc::clone(range);
+    let pad_pipeline = pipeline.downgrade();
+    demuxer.connect_pad_added(move |demuxer, pad| {
+        let Some(pipeline) = pad_pipeline.upgrade() else {
+            return;
+        };
+        let linked = sink_pad(pad)
+            .map(|sinkpad| pad.link(&sinkpad).is_ok())
+            .unwrap_or(false);
+        if linked {
+            drop_until_seeked(pad, &pad_range);
+            return;
+        }
+        link_to_fakesink(&pipeline, demuxer, pad);
+    });
+    // the seek is done by the export thread once all the streams are known
+    demuxer.connect_no_more_pads(move |element| {
+        element
+            .post_message(
+                gst::message::Application::builder(gst::Structure::new_empty("no-more-pads"))
+                    .src(element)
+                    .build(),
+            )
+            .ok();
+    });
+}
+
+fn start_export(
+    pipeline: gst::Pipeline,
+    (start, end): (std::time::Duration, std::time::Duration),
+    seek_flags: gst::SeekFlags,
+    range: Arc<StreamRange>,
+    output: &Path,
+    report_range: bool,
+) -> Result<ClipExport, IcedGStreamerError> {
+    pipeline.set_state(gst::State::Playing)?;
+
+    let (sd, rv) = mpsc::channel::<ExportMessage>(100);
+    let export = ClipExport {
+        id: next_id(),
+        rv: Arc::new(AsyncMutex::new(rv)),
+        cancelled: Arc::new(AtomicBool::new(false)),
+        outcome: Arc::new(Mutex::new(None)),
+        error: Arc::new(Mutex::new(None)),
+    };
+    let worker = ExportWorker {
+        pipeline,
+        start: gst::ClockTime::from_nseconds(start.as_nanos() as u64),
+        end: gst::ClockTime::from_nseconds(end.as_nanos() as u64),
+        seek_flags,
+        range,
+        report_range,
+        output: output.to_owned(),
+        sd,
+        cancelled: Arc::clone(&export.cancelled),
+        outcome: Arc::clone(&export.outcome),
+        error: Arc::clone(&export.error),
+    };
+    std::thread::spawn(move || worker.run());
+    Ok(export)
+}
+
+// drop the data before the flush of the seek, after it record the range the stream covers, the
+// buffer times are turned into the media position by the segment of the seek, they carry the
+// offset of the source, like the one of mpeg-ts, and the start is where the demuxer snapped to
+fn drop_until_seeked(pad: &gst::Pad, range: &Arc<StreamRange>) {
+    let seeked = AtomicBool::new(false);
+    let segment: Mutex<Option<gst::FormattedSegment<gst::ClockTime>>> = Mutex::new(None);
+    let range = Arc::clone(range);
+    pad.add_probe(
+        gst::PadProbeType::BUFFER
+            | gst::PadProbeType::BUFFER_LIST
+            | gst::PadProbeType::EVENT_DOWNSTREAM
+            | gst::PadProbeType::EVENT_FLUSH,
+        move |_, info| match &info.data {
+            Some(gst::PadProbeData::Event(event)) => {
+                match event.view() {
+                    gst::EventView::FlushStop(_) => seeked.store(true, Ordering::Relaxed),
+                    gst::EventView::Segment(event) if seeked.load(Ordering::Relaxed) => {
+                        let Some(time_segment) = event.segment().downcast_ref::<gst::ClockTime>()
+                        else {
+                            return gst::PadProbeReturn::Ok;
+                        };
+                        if let Some(start) = time_segment
+                            .start()
+                            .and_then(|start| time_segment.to_stream_time(start))
+                        {
+                            range.first.fetch_min(start.nseconds(), Ordering::Relaxed);
+                        }
+                        if let Ok(mut segment) = segment.lock() {
+                            *segment = Some(time_segment.clone());
+                        }
+                    }
+                    _ => {}
+                }
+                gst::PadProbeReturn::Ok
+            }
+            Some(gst::PadProbeData::Buffer(buffer)) if seeked.load(Ordering::Relaxed) => {
+                let end = buffer
+                    .pts()
+                    .map(|pts| pts + buffer.duration().unwrap_or(gst::ClockTime::ZERO));
+                let end = segment.lock().ok().and_then(|segment| {
+                    segment
+                        .as_ref()
+                        .zip(end)
+                        .and_then(|(segment, end)| segment.to_stream_time(end))
+                });
+                if let Some(end) = end {
+                    range.last.fetch_max(end.nseconds(), Ordering::Relaxed);
+                }
+                gst::PadProbeReturn::Ok
+            }
+            Some(gst::PadProbeData::Buffer(_)) | Some(gst::PadProbeData::BufferList(_))
+                if !seeked.load(Ordering::Relaxed) =>
+            {
+                gst::PadProbeReturn::Drop
+            }
+            _ => gst::PadProbeReturn::Ok,
+        },
+    );
+}
+
+struct ExportWorker {
+    pipeline: gst::Pipeline,
+    start: gst::ClockTime,
+    end: gst::ClockTime,
+    seek_flags: gst::SeekFlags,
+    range: Arc<StreamRange>,
+    report_range: bool,
+    output: std::path::PathBuf,
+    sd: mpsc::Sender<ExportMessage>,
+    cancelled: Arc<AtomicBool>,
+    outcome: Arc<Mutex<Option<ExportMessage>>>,
+    error: Arc<Mutex<Option<IcedGStreamerError>>>,
+}
+
+impl ExportWorker {
+    fn run(mut self) {
+        let Some(bus) = self.pipeline.bus() else {
+            self.fail(IcedGStreamerError::Bus);
+            return;
+        };
+        let mut seeked = false;
+        loop {
+            if self.cancelled.load(Ordering::Relaxed) {
+                self.finish(ExportMessage::Cancelled);
+                return;
+            }
+            match bus
+                .timed_pop(PROGRESS_INTERVAL)
+                .as_ref()
+                .map(|msg| msg.view())
+            {
+                Some(gst::MessageView::Application(app))
+                    if !seeked
+                        && app
+                            .structure()
+                            .map(|s| s.name() == "no-more-pads")
+                            .unwrap_or(false) =>
+                {
+                    let demuxer = app.src().and_then(|src| src.downcast_ref::<gst::Element>());
+                    if !demuxer.map(|demuxer| self.seek(demuxer)).unwrap_or(false) {
+                        self.fail(IcedGStreamerError::SeekFailed(self.start));
+                        return;
+                    }
+                    seeked = true;
+                }
+                Some(gst::MessageView::Eos(_)) => {
+                    self.sd.try_send(ExportMessage::Progress(1.0)).ok();
+                    if self.report_range {
+                        let first = self.range.first.load(Ordering::Relaxed);
+                        let last = self.range.last.load(Ordering::Relaxed);
+                        self.sd
+                            .try_send(ExportMessage::Trimmed {
+                                start: std::time::Duration::from_nanos(first.min(last)),
+                                end: std::time::Duration::from_nanos(last),
+                            })
+                            .ok();
+                    }
+                    self.finish(ExportMessage::Finished);
+                    return;
+                }
+                Some(gst::MessageView::Error(err)) => {
+                    self.fail(IcedGStreamerError::from_bus_error(err));
+                    return;
+                }
+                Some(gst::MessageView::Element(element)) => {
+                    if let Some(missing) = MissingPlugin::from_message(element.message()) {
+                        self.fail(IcedGStreamerError::MissingPlugin(missing));
+                        return;
+                    }
+                }
+                _ => {}
+            }
+            if seeked {
+                let position =
+                    gst::ClockTime::from_nseconds(self.range.last.load(Ordering::Relaxed));
+                let progress = position.saturating_sub(self.start).nseconds() as f64
+                    / (self.end - self.start).nseconds() as f64;
+                self.sd
+                    .try_send(ExportMessage::Progress(progress.clamp(0.0, 1.0)))
+                    .ok();
+            }
+        }
+    }
+
+    // the muxers like matroskamux refuse the seek going up through them, so the demuxer is
+    // seeked by one of its pads, it seeks all the streams
+    fn seek(&self, demuxer: &gst::Element) -> bool {
+        let seek = gst::event::Seek::new(
+            1.0,
+            self.seek_flags,
+            gst::SeekType::Set,
+            self.start,
+            gst::SeekType::Set,
+            self.end,
+        );
+        demuxer
+            .src_pads()
+            .iter()
+            .any(|pad| pad.send_event(seek.clone()))
+    }
+
+    fn fail(&mut self, err: IcedGStreamerError) {
+        if let Ok(mut error) = self.error.lock() {
+            *error = Some(err);
+        }
+        self.finish(ExportMessage::Failed);
+    }
+
+    // the file is closed when the pipeline goes to null, so it is done before the outcome is
+    // known
+    fn finish(&mut self, message: ExportMessage) {
+        self.pipeline.set_state(gst::State::Null).ok();
+        if message != ExportMessage::Finished {
+            std::fs::remove_file(&self.output).ok();
+        }
+        if let Ok(mut outcome) = self.outcome.lock() {
+            *outcome = Some(message);
+        }
+        self.sd.try_send(message).ok();
+    }
+}
+
+impl ClipExport {
+    /// stop the export, [ExportMessage::Cancelled] comes after it is stopped
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// how the export ended, [None] if it is still running
+    pub fn outcome(&self) -> Option<ExportMessage> {
+        self.outcome.lock().map(|outcome| *outcome).unwrap_or(None)
+    }
+
+    /// take the error when the export failed
+    pub fn take_error(&self) -> Option<IcedGStreamerError> {
+        self.error
+            .lock()
+            .map(|mut error| error.take())
+            .unwrap_or(None)
+    }
+
+    /// wait until the export ends, it can be used by [iced::Command::perform]
+    pub fn wait(&self) -> impl std::future::Future<Output = ExportMessage> + Send + 'static {
+        let outcome = self.outcome.clone();
+        async move {
+            loop {
+                if let Some(message) = outcome.lock().map(|outcome| *outcome).unwrap_or(None) {
+                    return message;
+                }
+                futures_time::task::sleep(futures_time::time::Duration::from_millis(
+                    PROGRESS_INTERVAL.mseconds(),
+                ))
+                .await;
+            }
+        }
+    }
+
+    /// get the subscription of the progress, you can use in iced::subscription
+    pub fn subscription(&self) -> iced::Subscription<ExportMessage> {
+        let rv = self.rv.clone();
+        iced::subscription::channel(
+            (std::any::TypeId::of::<ClipExport>(), self.id),
+            100,
+            |mut output| async move {
+                let mut rv = rv.lock().await;
+                while let Some(message) = rv.next().await {
+                    let _ = output.send(message).await;
+                }
+                loop {
+                    // DO NOTHING here
+                    futures_time::task::sleep(futures_time::time::Duration::from_millis(10)).await;
+                }
+            },
+        )
+    }
+}
+
+impl GstreamerIcedBase {
+    /// [export_animation] of the media playing with its overlays, the export runs beside the
+    /// playback, it can be waited by [iced::Command::perform] with [ClipExport::wait]
+    pub fn export_animation(
+        &self,
+        start: std::time::Duration,
+        end: std::time::Duration,
+        options: AnimationOptions,
+        output: &Path,
+    ) -> Result<ClipExport, IcedGStreamerError> {
+        let uri = self
+            .source
+            .property::<Option<String>>("current-uri")
+            .or_else(|| self.source.property::<Option<String>>("uri"))
+            .ok_or(IcedGStreamerError::Uri)?;
+        let url = url::Url::parse(&uri).map_err(|_| IcedGStreamerError::Uri)?;
+        // the data of appsrc is only pushed to the player
+        if url.scheme() == "appsrc" {
+            return Err(IcedGStreamerError::Uri);
+        }
+        export_animation(&url, start, end, options, self.overlay(), output)
+    }
+}
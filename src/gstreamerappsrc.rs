@@ -0,0 +1,290 @@
+use futures::channel::mpsc;
+use gst::prelude::*;
+use gstreamer as gst;
+use gstreamer_app as gst_app;
+use gstreamer_audio as gst_audio;
+use iced::Command;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use super::export::ExportProfile;
+use super::plugins::make_element;
+use super::video::video_output;
+use super::{
+    static_pad, FrameData, GStreamerMessage, GstreamerIced, IcedGStreamerError, PlayStatus,
+};
+
+pub type GstreamerIcedAppSrc = GstreamerIced<3>;
+
+// how much of the pushed data can wait in the appsrc, pushing more fails instead of growing
+// the memory without a limit
+const QUEUED_SECONDS: u64 = 1;
+
+/// the format of the audio pushed by [GstreamerIcedAppSrc::push_audio], the samples are f32 and
+/// interleaved
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PcmFormat {
+    pub rate: u32,
+    pub channels: u32,
+}
+
+/// the appsrc elements the frames and samples are pushed into, with the count of them to make
+/// the timestamps
+#[derive(Debug)]
+pub(crate) struct AppSource {
+    video: gst_app::AppSrc,
+    framerate: u32,
+    frames: u64,
+    audio: Option<(gst_app::AppSrc, PcmFormat)>,
+    samples: u64,
+}
+
+// the stream goes to the pad encodebin has for it, or to fakesink when the profile drops it
+fn link_to_encoder(
+    pipeline: &gst::Pipeline,
+    last: &gst::Element,
+    encodebin: &gst::Element,
+    template: &str,
+) -> Result<(), IcedGStreamerError> {
+    let srcpad = static_pad(last, "src")?;
+    let sinkpad = match encodebin.request_pad_simple(template) {
+        Some(pad) => pad,
+        None => {
+            let fakesink = make_element("fakesink")?.build()?;
+            pipeline.add(&fakesink)?;
+            static_pad(&fakesink, "sink")?
+        }
+    };
+    srcpad
+        .link(&sinkpad)
+        .map_err(|err| IcedGStreamerError::link(&srcpad, &sinkpad, err))?;
+    Ok(())
+}
+
+impl GstreamerIcedAppSrc {
+    /// make a player of the frames generated by rust, push them by [Self::push_frame] at the
+    /// framerate, with [PcmFormat] the audio can be pushed by [Self::push_audio]
+    pub fn new_appsrc(
+        framerate: u32,
+        audio: Option<PcmFormat>,
+    ) -> Result<Self, IcedGStreamerError> {
+        Self::build_appsrc(framerate, audio, None)
+    }
+
+    /// like [Self::new_appsrc], but the frames and the audio are encoded into the output file
+    /// instead of shown, the filters and the overlays are applied, the file is complete after
+    /// [Self::end_of_stream] when [GStreamerMessage::BusGoToEnd] comes, dropping the player
+    /// before it leaves the file cut
+    pub fn new_appsrc_export(
+        framerate: u32,
+        audio: Option<PcmFormat>,
+        profile: ExportProfile,
+        output: &Path,
+    ) -> Result<Self, IcedGStreamerError> {
+        Self::build_appsrc(framerate, audio, Some((profile, output)))
+    }
+
+    fn build_appsrc(
+        framerate: u32,
+        audio: Option<PcmFormat>,
+        export: Option<(ExportProfile, &Path)>,
+    ) -> Result<Self, IcedGStreamerError> {
+        gst::init()?;
+
+        let source = gst::Pipeline::new();
+        let video = gst_app::AppSrc::builder()
+            .name("video_src")
+            .format(gst::Format::Time)
+            .build();
+
+        let frame: Arc<Mutex<Option<FrameData>>> = Arc::new(Mutex::new(None));
+
+        let (sd, rv) = mpsc::channel::<GStreamerMessage>(100);
+
+        let (video_filter, mut video_elements) = video_output(&frame, sd)?;
+        let encodebin = match export {
+            Some((profile, output)) => {
+                // the frames are encoded after the filters, instead of scaled for the display
+                video_elements.truncate(video_elements.len() - 2);
+                let location = output.to_string_lossy();
+                let encodebin = make_element("encodebin")?
+                    .property("profile", profile.encoding_profile()?)
+                    .build()?;
+                let filesink = make_element("filesink")?
+                    .property("location", location.as_ref())
+                    .build()?;
+                source.add_many([&encodebin, &filesink])?;
+                encodebin.link(&filesink)?;
+                Some(encodebin)
+            }
+            None => None,
+        };
+        let mut elements = vec![video.clone().upcast()];
+        elements.extend(video_elements);
+
+        source.add_many(&elements)?;
+        gst::Element::link_many(&elements)?;
+        if let (Some(encodebin), Some(last)) = (encodebin.as_ref(), elements.last()) {
+            link_to_encoder(&source, last, encodebin, "video_%u")?;
+        }
+
+        let audio = match audio {
+            Some(format) => {
+                let audio_src = gst_app::AppSrc::builder()
+                    .name("audio_src")
+                    .format(gst::Format::Time)
+                    .caps(
+                        &gst_audio::AudioCapsBuilder::new_interleaved()
+                            .format(gst_audio::AudioFormat::F32le)
+                            .rate(format.rate as i32)
+                            .channels(format.channels as i32)
+                            .build(),
+                    )
+                    .max_bytes(
+                        QUEUED_SECONDS
+                            * format.rate.max(1) as u64
+                            * format.channels.max(1) as u64
+                            * 4,
+                    )
+                    .build();
+                let mut audio_elements = vec![
+                    audio_src.clone().upcast(),
+                    make_element("audioconvert")?.build()?,
+                    make_element("audioresample")?.build()?,
+                ];
+                if encodebin.is_none() {
+                    audio_elements.push(make_element("autoaudiosink")?.build()?);
+                }
+                source.add_many(&audio_elements)?;
+                gst::Element::link_many(&audio_elements)?;
+                if let (Some(encodebin), Some(last)) = (encodebin.as_ref(), audio_elements.last()) {
+                    link_to_encoder(&source, last, encodebin, "audio_%u")?;
+                }
+                Some((audio_src, format))
+            }
+            None => None,
+        };
+
+        source.set_state(gst::State::Playing)?;
+
+        let mut player =
+            Self::from_source(source.into(), frame, rv, video_filter, PlayStatus::Playing)?;
+        player.app_source = Some(AppSource {
+            video,
+            framerate: framerate.max(1),
+            frames: 0,
+            audio,
+            samples: 0,
+        });
+        Ok(player)
+    }
+
+    fn app_source(&mut self) -> Result<&mut AppSource, IcedGStreamerError> {
+        self.app_source
+            .as_mut()
+            .ok_or_else(|| IcedGStreamerError::MissingElement("appsrc".to_owned()))
+    }
+
+    /// push a RGBA frame, it is shown after the one pushed before it, the size can change
+    /// between the frames, it fails with [IcedGStreamerError::QueueFull] when a second of frames
+    /// is waiting, the frame is dropped then, push at the framerate or try it again later
+    pub fn push_frame(&mut self, frame: FrameData) -> Result<(), IcedGStreamerError> {
+        let expected = frame.width as usize * frame.height as usize * 4;
+        if frame.pixels.len() != expected {
+            return Err(IcedGStreamerError::InvalidFrame {
+                expected,
+                actual: frame.pixels.len(),
+            });
+        }
+        let app_source = self.app_source()?;
+        let caps = gst::Caps::builder("video/x-raw")
+            .field("format", "RGBA")
+            .field("width", frame.width as i32)
+            .field("height", frame.height as i32)
+            .field(
+                "framerate",
+                gst::Fraction::new(app_source.framerate as i32, 1),
+            )
+            .build();
+        if app_source.video.caps().as_ref() != Some(&caps) {
+            app_source.video.set_caps(Some(&caps));
+            app_source
+                .video
+                .set_max_bytes(expected as u64 * QUEUED_SECONDS * app_source.framerate as u64);
+        }
+        if app_source.video.current_level_bytes() >= app_source.video.max_bytes() {
+            return Err(IcedGStreamerError::QueueFull("video"));
+        }
+
+        let frame_duration = gst::ClockTime::SECOND / app_source.framerate as u64;
+        let mut buffer = gst::Buffer::from_mut_slice(frame.pixels);
+        if let Some(buffer) = buffer.get_mut() {
+            buffer.set_pts(frame_duration * app_source.frames);
+            buffer.set_duration(frame_duration);
+        }
+        app_source.video.push_buffer(buffer)?;
+        app_source.frames += 1;
+        Ok(())
+    }
+
+    /// push the interleaved f32 samples in the [PcmFormat] given when the player is made, like
+    /// [Self::push_frame] it fails with [IcedGStreamerError::QueueFull] when a second of audio
+    /// is waiting
+    pub fn push_audio(&mut self, samples: &[f32]) -> Result<(), IcedGStreamerError> {
+        let app_source = self.app_source()?;
+        let (audio_src, format) = app_source
+            .audio
+            .as_ref()
+            .ok_or(IcedGStreamerError::AudioSourceUnavailable)?;
+        let channels = format.channels.max(1) as u64;
+        let rate = format.rate.max(1) as u64;
+        let frames = samples.len() as u64 / channels;
+        if audio_src.current_level_bytes() >= audio_src.max_bytes() {
+            return Err(IcedGStreamerError::QueueFull("audio"));
+        }
+
+        let data: Vec<u8> = samples
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect();
+        let mut buffer = gst::Buffer::from_mut_slice(data);
+        if let Some(buffer) = buffer.get_mut() {
+            let pts = gst::ClockTime::SECOND.mul_div_floor(app_source.samples, rate);
+            let end = gst::ClockTime::SECOND.mul_div_floor(app_source.samples + frames, rate);
+            if let (Some(pts), Some(end)) = (pts, end) {
+                buffer.set_pts(pts);
+                buffer.set_duration(end - pts);
+            }
+        }
+        audio_src.push_buffer(buffer)?;
+        app_source.samples += frames;
+        Ok(())
+    }
+
+    /// nothing more is pushed, [GStreamerMessage::BusGoToEnd] comes after the pushed data is
+    /// played
+    pub fn end_of_stream(&mut self) -> Result<(), IcedGStreamerError> {
+        let app_source = self.app_source()?;
+        app_source.video.end_of_stream()?;
+        if let Some((audio_src, _)) = app_source.audio.as_ref() {
+            audio_src.end_of_stream()?;
+        }
+        Ok(())
+    }
+
+    /// update for appsrc
+    pub fn update(&mut self, message: GStreamerMessage) -> iced::Command<GStreamerMessage> {
+        match message {
+            GStreamerMessage::PlayStatusChanged(status) => {
+                if let Err(err) = self.set_play_status(status) {
+                    return self.report_error(err);
+                }
+            }
+            GStreamerMessage::BusGoToEnd | GStreamerMessage::BusError => {
+                self.play_status = PlayStatus::End;
+            }
+            _ => {}
+        }
+        Command::none()
+    }
+}
@@ -1,13 +1,22 @@
 use futures::channel::mpsc;
+use gst::glib;
 use gst::prelude::*;
 use gst::GenericFormattedValue;
+use gst_audio::prelude::StreamVolumeExt;
 use gstreamer as gst;
-use gstreamer_app as gst_app;
+use gstreamer_audio as gst_audio;
+use iced::widget::image;
 use iced::Command;
-use smol::lock::Mutex as AsyncMutex;
 use std::sync::{Arc, Mutex};
 
-use super::{FrameData, GStreamerMessage, GstreamerIced, IcedGStreamerError, PlayStatus, Position};
+use super::adaptive::AdaptiveStreaming;
+use super::audio::AudioFilter;
+use super::plugins::make_element;
+use super::video::video_output;
+use super::{
+    static_pad, FrameData, GStreamerMessage, GstreamerIced, IcedGStreamerError, PlayStatus,
+    Position, RtspStreamState, VisualizationMode, VolumeScale, MAX_VOLUME,
+};
 
 pub type GstreamerIcedBase = GstreamerIced<0>;
 
@@ -29,77 +38,153 @@ impl GstreamerIcedBase {
 
     /// accept url like from local or from http
     pub fn new_url(url: &url::Url, islive: bool) -> Result<Self, IcedGStreamerError> {
+        Self::new_playbin(url.as_str(), islive, |_, _| {})
+    }
+
+    /// build the playbin, `source_setup` is called when playbin creates the source element,
+    /// with it the source can be configured and send messages
+    pub(crate) fn new_playbin<F>(
+        uri: &str,
+        islive: bool,
+        source_setup: F,
+    ) -> Result<Self, IcedGStreamerError>
+    where
+        F: Fn(&gst::Element, &mpsc::Sender<GStreamerMessage>) + Send + Sync + 'static,
+    {
         gst::init()?;
 
         let video_sink = gst::Bin::new();
-        let videoconvert = gst::ElementFactory::make("videoconvert").build()?;
-        let videoscale = gst::ElementFactory::make("videoscale").build()?;
-
-        let app_sink_caps = gst::Caps::builder("video/x-raw")
-            .field("format", "RGBA")
-            .field("pixel-aspect-ratio", gst::Fraction::new(1, 1))
-            .build();
-
-        let app_sink: gst_app::AppSink = gst_app::AppSink::builder()
-            .name("app_sink")
-            .caps(&app_sink_caps)
-            .build();
-
         let frame: Arc<Mutex<Option<FrameData>>> = Arc::new(Mutex::new(None));
-        let frame_ref = Arc::clone(&frame);
-
-        let (mut sd, rv) = mpsc::channel::<GStreamerMessage>(100);
-
-        app_sink.set_callbacks(
-            gst_app::AppSinkCallbacks::builder()
-                .new_sample(move |sink| {
-                    let sample = sink.pull_sample().map_err(|_| gst::FlowError::Eos)?;
-                    let buffer = sample.buffer().ok_or(gst::FlowError::Error)?;
-                    let map = buffer.map_readable().map_err(|_| gst::FlowError::Error)?;
-
-                    let caps = sample.caps().ok_or(gst::FlowError::Error)?;
-                    let s = caps.structure(0).ok_or(gst::FlowError::Error)?;
-                    let width = s.get::<i32>("width").map_err(|_| gst::FlowError::Error)?;
-                    let height = s.get::<i32>("height").map_err(|_| gst::FlowError::Error)?;
-                    *frame_ref.lock().map_err(|_| gst::FlowError::Error)? = Some(FrameData {
-                        width: width as _,
-                        height: height as _,
-                        pixels: map.as_slice().to_owned(),
-                    });
-                    sd.try_send(GStreamerMessage::FrameUpdate).ok();
-                    Ok(gst::FlowSuccess::Ok)
-                })
-                .build(),
-        );
 
-        let app_sink: gst::Element = app_sink.into();
+        let (sd, rv) = mpsc::channel::<GStreamerMessage>(100);
+        let volume_sd = sd.clone();
+        let setup_sd = sd.clone();
+        let adaptive_sd = sd.clone();
 
-        video_sink.add_many([&videoconvert, &videoscale, &app_sink])?;
-        gst::Element::link_many([&videoconvert, &videoscale, &app_sink])?;
+        let (video_filter, video_elements) = video_output(&frame, sd)?;
 
-        let staticpad = videoconvert.static_pad("sink").unwrap();
+        video_sink.add_many(&video_elements)?;
+        gst::Element::link_many(&video_elements)?;
+
+        let staticpad = static_pad(&video_elements[0], "sink")?;
         let sinkgost = gst::GhostPad::builder_with_target(&staticpad)?.build();
         sinkgost.set_active(true)?;
         video_sink.add_pad(&sinkgost)?;
 
-        let videosource = gst::ElementFactory::make("playbin")
-            .property("uri", url.as_str())
+        let videosource = make_element("playbin")?
+            .property("uri", uri)
             .property("video-sink", video_sink.to_value())
             .build()?;
 
-        let source = videosource.downcast::<gst::Bin>().unwrap();
+        let audio_filter = match AudioFilter::new()? {
+            Some((audio_filter, filter_bin)) => {
+                videosource.set_property("audio-filter", filter_bin.to_value());
+                Some(audio_filter)
+            }
+            None => None,
+        };
+
+        videosource.connect_notify(Some("volume"), move |playbin, _| {
+            let volume: f64 = playbin.property("volume");
+            volume_sd
+                .clone()
+                .try_send(GStreamerMessage::VolumeChanged(volume))
+                .ok();
+        });
+        let volume = videosource.property("volume");
+
+        videosource.connect("source-setup", false, move |args| {
+            if let Some(Ok(element)) = args.get(1).map(|arg| arg.get::<gst::Element>()) {
+                source_setup(&element, &setup_sd);
+            }
+            None
+        });
+
+        let source = videosource.downcast::<gst::Bin>().map_err(|element| {
+            IcedGStreamerError::WrongType {
+                element: element.name().to_string(),
+                expected: "bin",
+            }
+        })?;
+        let adaptive = AdaptiveStreaming::watch(&source, &staticpad, adaptive_sd);
 
-        Ok(Self {
-            frame,
-            bus: source.bus().unwrap(),
-            source,
-            play_status: PlayStatus::Stop,
-            rv: Arc::new(AsyncMutex::new(rv)),
-            duration: std::time::Duration::from_nanos(0),
-            position: std::time::Duration::from_nanos(0),
-            info_get_started: !islive,
-            volume: 0_f64,
-        })
+        let mut player = Self::from_source(source, frame, rv, video_filter, PlayStatus::Stop)?;
+        player.info_get_started = !islive;
+        player.is_live = islive;
+        player.volume = volume;
+        player.media_checked = false;
+        player.audio_filter = audio_filter;
+        player.adaptive = adaptive;
+        Ok(player)
+    }
+
+    /// set what to show when the media only contains audio, the visualiser only takes effect
+    /// when set before the media starts playing
+    pub fn set_visualization(&mut self, mode: VisualizationMode) -> Result<(), IcedGStreamerError> {
+        let flags = self.source.property_value("flags");
+        let flags_class = glib::FlagsClass::with_type(flags.type_())
+            .ok_or(IcedGStreamerError::Property("flags"))?;
+        let flags_builder = flags_class
+            .builder_with_value(flags)
+            .ok_or(IcedGStreamerError::Property("flags"))?;
+        let flags_builder = match mode {
+            VisualizationMode::Visualizer(visualizer) => {
+                let vis_plugin = make_element(visualizer.factory_name())?.build()?;
+                self.source.set_property("vis-plugin", &vis_plugin);
+                flags_builder.set_by_nick("vis")
+            }
+            _ => flags_builder.unset_by_nick("vis"),
+        };
+        let flags = flags_builder
+            .build()
+            .ok_or(IcedGStreamerError::Property("flags"))?;
+        self.source.set_property_from_value("flags", &flags);
+
+        self.visualization = mode;
+        self.cover_art = None;
+        if self.media_checked {
+            self.load_cover_art();
+        }
+        Ok(())
+    }
+
+    // playbin knows the streams after preroll, so check it once the pipeline is paused
+    fn check_media_kind(&mut self) {
+        let (_, state, _) = self.source.state(gst::ClockTime::ZERO);
+        if state < gst::State::Paused {
+            return;
+        }
+        let n_video: i32 = self.source.property("n-video");
+        let n_audio: i32 = self.source.property("n-audio");
+        self.audio_only = n_video == 0 && n_audio > 0;
+        self.media_checked = true;
+        self.load_cover_art();
+    }
+
+    fn load_cover_art(&mut self) {
+        if !self.audio_only || self.visualization != VisualizationMode::CoverArt {
+            return;
+        }
+        let Some(tags) = self
+            .source
+            .emit_by_name::<Option<gst::TagList>>("get-audio-tags", &[&0i32])
+        else {
+            return;
+        };
+        let Some(sample) = tags
+            .get::<gst::tags::Image>()
+            .or_else(|| tags.get::<gst::tags::PreviewImage>())
+            .map(|image| image.get())
+        else {
+            return;
+        };
+        let Some(map) = sample
+            .buffer()
+            .and_then(|buffer| buffer.map_readable().ok())
+        else {
+            return;
+        };
+        self.cover_art = Some(image::Handle::from_memory(map.as_slice().to_owned()));
     }
 
     // update for gstreamer base
@@ -110,10 +195,10 @@ impl GstreamerIcedBase {
                 if self.info_get_started {
                     loop {
                         // FIXME: move it to stream listener
-                        self.source
-                            .state(gst::ClockTime::from_seconds(5))
-                            .0
-                            .unwrap();
+                        if let Err(err) = self.source.state(gst::ClockTime::from_seconds(5)).0 {
+                            self.info_get_started = false;
+                            return self.report_error(err.into());
+                        }
 
                         if let Some(time) = self.source.query_duration::<gst::ClockTime>() {
                             self.duration = std::time::Duration::from_nanos(time.nseconds());
@@ -128,43 +213,139 @@ impl GstreamerIcedBase {
                             self.position = std::time::Duration::from_nanos(time.nseconds());
                             break;
                         }
-                        self.source
-                            .state(gst::ClockTime::from_seconds(5))
-                            .0
-                            .unwrap();
+                        if let Err(err) = self.source.state(gst::ClockTime::from_seconds(5)).0 {
+                            return self.report_error(err.into());
+                        }
                     }
                 }
-                self.volume = self.source.property("volume");
+                if !self.media_checked {
+                    self.check_media_kind();
+                }
             }
 
             GStreamerMessage::PlayStatusChanged(status) => {
-                match status {
-                    PlayStatus::Playing => {
-                        self.source.set_state(gst::State::Playing).unwrap();
-                    }
-                    PlayStatus::Stop => {
-                        self.source.set_state(gst::State::Paused).unwrap();
-                    }
-                    _ => {}
+                if let Err(err) = self.set_play_status(status) {
+                    return self.report_error(err);
+                }
+            }
+            GStreamerMessage::BusGoToEnd | GStreamerMessage::BusError => {
+                for state in self.rtsp_streams.iter_mut() {
+                    *state = RtspStreamState::Disconnected;
+                }
+                return self.on_stream_lost();
+            }
+            GStreamerMessage::Buffering(percent) => {
+                self.on_buffering(percent);
+                if percent >= 100 {
+                    return self.on_recovered();
                 }
-                self.play_status = status;
             }
-            GStreamerMessage::BusGoToEnd => {
-                self.play_status = PlayStatus::End;
+            GStreamerMessage::Reconnect => {
+                return self.reconnect();
+            }
+            GStreamerMessage::PipelinePlaying => {
+                return self.on_recovered();
+            }
+            GStreamerMessage::RtspStreamChanged { stream, state } => {
+                self.update_rtsp_stream(stream, state);
+            }
+            GStreamerMessage::VolumeChanged(volume) => {
+                self.volume = volume;
+            }
+            GStreamerMessage::VariantSwitched(index) => {
+                self.current_variant = Some(index);
             }
             _ => {}
         }
         Command::none()
     }
 
+    // pause until the buffer is full, live sources cannot be paused so they are left playing
+    fn on_buffering(&mut self, percent: i32) {
+        self.buffering_percent = percent;
+        if self.is_live {
+            return;
+        }
+        let (state, status) = match self.play_status {
+            PlayStatus::Playing if percent < 100 => (gst::State::Paused, PlayStatus::Buffering),
+            PlayStatus::Buffering if percent >= 100 => (gst::State::Playing, PlayStatus::Playing),
+            _ => return,
+        };
+        if self.source.set_state(state).is_ok() {
+            self.play_status = status;
+        }
+    }
+
+    /// how much of the buffer is filled, in percent
+    pub fn buffering_percent(&self) -> i32 {
+        self.buffering_percent
+    }
+
+    /// the ranges of the media which are downloaded, can be used to draw the buffered region
+    /// on the seek bar
+    pub fn buffered_ranges(&self) -> Vec<(std::time::Duration, std::time::Duration)> {
+        let mut query = gst::query::Buffering::new(gst::Format::Percent);
+        if !self.source.query(&mut query) {
+            return Vec::new();
+        }
+        let to_position = |value: GenericFormattedValue| {
+            let fraction = value.value().max(0) as f64 / *gst::format::Percent::MAX as f64;
+            self.duration.mul_f64(fraction.min(1.0))
+        };
+        query
+            .ranges()
+            .into_iter()
+            .map(|(start, stop)| (to_position(start), to_position(stop)))
+            .collect()
+    }
+
     /// get the volume of the video
     pub fn volume(&self) -> f64 {
         self.volume
     }
 
-    /// only can be set when source is video
+    /// set the linear volume, it is clamped between 0 and [MAX_VOLUME]
     pub fn set_volume(&mut self, volume: f64) {
-        self.source.set_property("volume", volume);
+        self.source
+            .set_property("volume", volume.clamp(0.0, MAX_VOLUME));
+    }
+
+    /// get the volume in the given [VolumeScale]
+    pub fn volume_scaled(&self, scale: VolumeScale) -> f64 {
+        gst_audio::StreamVolume::convert_volume(
+            VolumeScale::Linear.into(),
+            scale.into(),
+            self.volume,
+        )
+    }
+
+    /// set the volume in the given [VolumeScale], like [Self::set_volume], it is clamped to
+    /// [MAX_VOLUME]
+    pub fn set_volume_scaled(&mut self, scale: VolumeScale, volume: f64) {
+        let volume = gst_audio::StreamVolume::convert_volume(
+            scale.into(),
+            VolumeScale::Linear.into(),
+            volume,
+        );
+        self.set_volume(volume);
+    }
+
+    /// if the audio is muted
+    pub fn is_muted(&self) -> bool {
+        self.stream_volume()
+            .map(|stream_volume| stream_volume.is_muted())
+            .unwrap_or(false)
+    }
+
+    /// mute or unmute the audio, the volume is kept
+    pub fn set_muted(&mut self, muted: bool) {
+        if let Some(stream_volume) = self.stream_volume() {
+            stream_volume.set_mute(muted);
+        }
+    }
+
+    fn stream_volume(&self) -> Option<&gst_audio::StreamVolume> {
+        self.source.dynamic_cast_ref::<gst_audio::StreamVolume>()
     }
 
     /// get the duration, if is live or pipewire, it is 0
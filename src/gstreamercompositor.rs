@@ -0,0 +1,211 @@
+use futures::channel::mpsc;
+use gst::prelude::*;
+use gstreamer as gst;
+use iced::Command;
+use std::sync::{Arc, Mutex};
+
+use super::plugins::{link_to_fakesink, make_element};
+use super::video::video_output;
+use super::{
+    is_video, pad_caps, static_pad, FrameData, GStreamerMessage, GstreamerIced, IcedGStreamerError,
+    PlayStatus,
+};
+
+pub type GstreamerIcedCompositor = GstreamerIced<2>;
+
+/// where the video of a [CompositorInput] comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompositorSource {
+    /// a file or a network stream, only the video is used
+    Url(url::Url),
+    /// a pipewire node, like the one from ashpd
+    Pipewire(u32),
+    /// a v4l2 camera device like `/dev/video0`, [None] lets gstreamer choose one
+    Camera(Option<String>),
+}
+
+/// where and how an input is drawn on the output frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InputLayout {
+    pub x: i32,
+    pub y: i32,
+    /// [None] keeps the width of the input
+    pub width: Option<u32>,
+    /// [None] keeps the height of the input
+    pub height: Option<u32>,
+    /// the input with bigger zorder is drawn on the top
+    pub zorder: u32,
+    /// from 0.0 to 1.0
+    pub alpha: f64,
+}
+
+impl Default for InputLayout {
+    fn default() -> Self {
+        Self {
+            x: 0,
+            y: 0,
+            width: None,
+            height: None,
+            zorder: 0,
+            alpha: 1.0,
+        }
+    }
+}
+
+/// an input of [GstreamerIcedCompositor]
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompositorInput {
+    pub source: CompositorSource,
+    pub layout: InputLayout,
+}
+
+fn apply_layout(pad: &gst::Pad, layout: &InputLayout) {
+    pad.set_property("xpos", layout.x);
+    pad.set_property("ypos", layout.y);
+    pad.set_property("width", layout.width.unwrap_or(0) as i32);
+    pad.set_property("height", layout.height.unwrap_or(0) as i32);
+    pad.set_property("zorder", layout.zorder);
+    pad.set_property("alpha", layout.alpha.clamp(0.0, 1.0));
+}
+
+fn make_source(
+    source: &CompositorSource,
+    pipeline: &gst::Pipeline,
+    sinkpad: &gst::Pad,
+) -> Result<(), IcedGStreamerError> {
+    let element = match source {
+        CompositorSource::Url(url) => make_element("uridecodebin")?
+            .property("uri", url.as_str())
+            .build()?,
+        CompositorSource::Pipewire(path) => make_element("pipewiresrc")?
+            .property("path", path.to_string())
+            .build()?,
+        CompositorSource::Camera(Some(device)) => make_element("v4l2src")?
+            .property("device", device)
+            .build()?,
+        CompositorSource::Camera(None) => make_element("autovideosrc")?.build()?,
+    };
+    pipeline.add(&element)?;
+
+    // the inputs are converted to the raw formats the compositor takes
+    let convert = make_element("videoconvert")?.build()?;
+    pipeline.add(&convert)?;
+    let convert_src = static_pad(&convert, "src")?;
+    convert_src
+        .link(sinkpad)
+        .map_err(|err| IcedGStreamerError::link(&convert_src, sinkpad, err))?;
+
+    // the cameras may only give jpeg, so the devices are decoded like the urls
+    let decoder = match source {
+        CompositorSource::Url(_) => element,
+        _ => {
+            let decodebin = make_element("decodebin")?.build()?;
+            pipeline.add(&decodebin)?;
+            element.link(&decodebin)?;
+            decodebin
+        }
+    };
+
+    // the decoders only expose the pads after the media is found, the streams which are not
+    // video are dropped
+    let convert_sink = static_pad(&convert, "sink")?;
+    let pipeline = pipeline.downgrade();
+    decoder.connect_pad_added(move |element, srcpad| {
+        if is_video(&pad_caps(srcpad)) && !convert_sink.is_linked() {
+            srcpad.link(&convert_sink).ok();
+            return;
+        }
+        if let Some(pipeline) = pipeline.upgrade() {
+            link_to_fakesink(&pipeline, element, srcpad);
+        }
+    });
+    Ok(())
+}
+
+impl GstreamerIcedCompositor {
+    /// mix the inputs into one frame, like showing the camera on the top of the screen capture
+    pub fn new_compositor(inputs: &[CompositorInput]) -> Result<Self, IcedGStreamerError> {
+        gst::init()?;
+
+        let source = gst::Pipeline::new();
+        let compositor = make_element("compositor")?.name("compositor").build()?;
+
+        let frame: Arc<Mutex<Option<FrameData>>> = Arc::new(Mutex::new(None));
+
+        let (sd, rv) = mpsc::channel::<GStreamerMessage>(100);
+
+        let (video_filter, video_elements) = video_output(&frame, sd)?;
+        let mut elements = vec![compositor.clone()];
+        elements.extend(video_elements);
+
+        source.add_many(&elements)?;
+
+        gst::Element::link_many(&elements)?;
+
+        for (index, input) in inputs.iter().enumerate() {
+            let name = format!("sink_{index}");
+            let sinkpad = compositor.request_pad_simple(&name).ok_or_else(|| {
+                IcedGStreamerError::MissingPad {
+                    element: compositor.name().to_string(),
+                    pad: name.clone(),
+                }
+            })?;
+            apply_layout(&sinkpad, &input.layout);
+            make_source(&input.source, &source, &sinkpad)?;
+        }
+
+        source.set_state(gst::State::Playing)?;
+
+        Self::from_source(source.into(), frame, rv, video_filter, PlayStatus::Playing)
+    }
+
+    /// move, resize or reorder an input, it can be changed while playing
+    pub fn set_input_layout(
+        &mut self,
+        index: usize,
+        layout: InputLayout,
+    ) -> Result<(), IcedGStreamerError> {
+        let pad = self
+            .input_pad(index)
+            .ok_or(IcedGStreamerError::InvalidInput(index))?;
+        apply_layout(&pad, &layout);
+        Ok(())
+    }
+
+    /// the current [InputLayout] of an input
+    pub fn input_layout(&self, index: usize) -> Option<InputLayout> {
+        let pad = self.input_pad(index)?;
+        let width: i32 = pad.property("width");
+        let height: i32 = pad.property("height");
+        Some(InputLayout {
+            x: pad.property("xpos"),
+            y: pad.property("ypos"),
+            width: (width > 0).then_some(width as u32),
+            height: (height > 0).then_some(height as u32),
+            zorder: pad.property("zorder"),
+            alpha: pad.property("alpha"),
+        })
+    }
+
+    fn input_pad(&self, index: usize) -> Option<gst::Pad> {
+        self.source
+            .by_name("compositor")?
+            .static_pad(&format!("sink_{index}"))
+    }
+
+    /// update for compositor
+    pub fn update(&mut self, message: GStreamerMessage) -> iced::Command<GStreamerMessage> {
+        match message {
+            GStreamerMessage::PlayStatusChanged(status) => {
+                if let Err(err) = self.set_play_status(status) {
+                    return self.report_error(err);
+                }
+            }
+            GStreamerMessage::BusGoToEnd | GStreamerMessage::BusError => {
+                self.play_status = PlayStatus::End;
+            }
+            _ => {}
+        }
+        Command::none()
+    }
+}
@@ -0,0 +1,95 @@
+use gst::prelude::*;
+use gstreamer as gst;
+
+use super::{GstreamerIcedBase, IcedGStreamerError};
+
+/// the settings of the http source, they are applied to souphttpsrc
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpOptions {
+    /// sent with every request, like the auth headers
+    pub extra_headers: Vec<(String, String)>,
+    /// [None] keeps the user agent of gstreamer
+    pub user_agent: Option<String>,
+    /// in the form of `name=value`
+    pub cookies: Vec<String>,
+    /// the proxy url, [None] uses the proxy of the system
+    pub proxy: Option<String>,
+    pub proxy_id: Option<String>,
+    pub proxy_password: Option<String>,
+    /// check the tls certificate of the server, turn it off for self signed servers
+    pub ssl_strict: bool,
+    /// fail when the server gives no response in the time, it is rounded up to seconds
+    pub timeout: std::time::Duration,
+}
+
+impl Default for HttpOptions {
+    fn default() -> Self {
+        Self {
+            extra_headers: Vec::new(),
+            user_agent: None,
+            cookies: Vec::new(),
+            proxy: None,
+            proxy_id: None,
+            proxy_password: None,
+            ssl_strict: true,
+            timeout: std::time::Duration::from_secs(15),
+        }
+    }
+}
+
+impl HttpOptions {
+    fn extra_headers(&self) -> gst::Structure {
+        self.extra_headers
+            .iter()
+            .fold(
+                gst::Structure::builder("extra-headers"),
+                |builder, (name, value)| builder.field(name.as_str(), value),
+            )
+            .build()
+    }
+}
+
+impl GstreamerIcedBase {
+    /// like [GstreamerIcedBase::new_url], the http request is customized by [HttpOptions]
+    ///
+    /// the options are applied to the source created by playbin, the fragments of HLS and DASH
+    /// streams are downloaded by the demuxer and may not get them
+    pub fn new_url_with_http(
+        url: &url::Url,
+        islive: bool,
+        options: HttpOptions,
+    ) -> Result<Self, IcedGStreamerError> {
+        Self::new_playbin(url.as_str(), islive, move |source, _| {
+            if source
+                .factory()
+                .map(|factory| factory.name() != "souphttpsrc")
+                .unwrap_or(true)
+            {
+                return;
+            }
+            if !options.extra_headers.is_empty() {
+                source.set_property("extra-headers", options.extra_headers());
+            }
+            if let Some(user_agent) = options.user_agent.as_ref() {
+                source.set_property("user-agent", user_agent);
+            }
+            if !options.cookies.is_empty() {
+                let cookies: Vec<&str> = options.cookies.iter().map(String::as_str).collect();
+                source.set_property("cookies", gst::glib::StrV::from(cookies));
+            }
+            if let Some(proxy) = options.proxy.as_ref() {
+                source.set_property("proxy", proxy);
+            }
+            if let Some(proxy_id) = options.proxy_id.as_ref() {
+                source.set_property("proxy-id", proxy_id);
+            }
+            if let Some(proxy_password) = options.proxy_password.as_ref() {
+                source.set_property("proxy-pw", proxy_password);
+            }
+            source.set_property("ssl-strict", options.ssl_strict);
+            // 0 is no timeout for souphttpsrc, so a timeout under a second is rounded up
+            let timeout = options.timeout.as_secs() + (options.timeout.subsec_nanos() > 0) as u64;
+            source.set_property("timeout", timeout.clamp(1, u32::MAX as u64) as u32);
+        })
+    }
+}
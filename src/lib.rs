@@ -1,15 +1,34 @@
+mod adaptive;
+mod audio;
+mod encoder;
+mod error;
+mod export;
+mod gstreamerappsrc;
 mod gstreamerbase;
+mod gstreamercompositor;
 mod gstreamerpipewire;
+mod http;
+mod netclock;
+mod overlay;
+mod plugins;
+mod reader;
+mod reconnect;
+mod rtsp;
+mod streamoutput;
+mod syncgroup;
+mod video;
 
 use futures::channel::mpsc;
 use gst::glib;
 use gst::prelude::*;
 use gst::GenericFormattedValue;
 use gstreamer as gst;
+use gstreamer_audio as gst_audio;
 use iced::futures::SinkExt;
 use iced::futures::StreamExt;
 use iced::widget::image;
 use smol::lock::Mutex as AsyncMutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use thiserror::Error;
 
@@ -22,6 +41,62 @@ pub enum PlayStatus {
     Stop,
     Playing,
     End,
+    /// paused by the player until enough data is downloaded, it plays again after that
+    Buffering,
+}
+
+/// the max linear volume can be set, volume above it would amplify and may clip
+pub const MAX_VOLUME: f64 = 1.0;
+
+/// the scale used by [GstreamerIcedBase::set_volume_scaled]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VolumeScale {
+    /// the raw volume gstreamer uses
+    Linear,
+    /// the perceptual scale, suitable for volume sliders
+    Cubic,
+    /// decibel, 0.0 is the full volume
+    Db,
+}
+
+impl From<VolumeScale> for gst_audio::StreamVolumeFormat {
+    fn from(scale: VolumeScale) -> Self {
+        match scale {
+            VolumeScale::Linear => gst_audio::StreamVolumeFormat::Linear,
+            VolumeScale::Cubic => gst_audio::StreamVolumeFormat::Cubic,
+            VolumeScale::Db => gst_audio::StreamVolumeFormat::Db,
+        }
+    }
+}
+
+/// what to show when the media only contains audio
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VisualizationMode {
+    /// show nothing, [GstreamerIced::frame_handle] stays [None]
+    #[default]
+    None,
+    /// show the cover art embedded in the audio tags
+    CoverArt,
+    /// render the audio with a gstreamer visualiser
+    Visualizer(Visualizer),
+}
+
+/// the gstreamer visualisers can be used by [VisualizationMode::Visualizer]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visualizer {
+    Goom,
+    Wavescope,
+    Synaescope,
+}
+
+impl Visualizer {
+    fn factory_name(&self) -> &'static str {
+        match self {
+            Visualizer::Goom => "goom",
+            Visualizer::Wavescope => "wavescope",
+            Visualizer::Synaescope => "synaescope",
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -43,12 +118,92 @@ impl From<FrameData> for image::Handle {
     }
 }
 
+pub use adaptive::{StreamVariant, VariantSelection};
+pub use audio::{
+    audio_output_devices, AudioOutput, AudioOutputDevice, ReplayGain, EQUALIZER_BANDS,
+    EQUALIZER_MAX_GAIN, EQUALIZER_MIN_GAIN,
+};
+
+pub use encoder::{
+    available_encoders, make_encoder, EncoderInfo, EncoderSettings, H264Profile, H264Settings,
+    H264Tune, VideoCodec, VpxDeadline, VpxSettings,
+};
+pub use error::{ErrorDomain, ErrorKind};
+pub use export::{
+    export_animation, export_clip, trim_clip, AnimationFormat, AnimationOptions, ClipExport,
+    ExportMessage, ExportProfile, RemuxContainer,
+};
+pub use http::HttpOptions;
+pub use netclock::NetClock;
+pub use overlay::{
+    FontOptions, HorizontalAlign, ImageOverlay, OverlayConfig, OverlayPosition, TextOverlay,
+    TimeMode, TimeOverlay, VerticalAlign,
+};
+pub use plugins::{check_requirements, install_missing_plugins, MissingPlugin, PluginStatus};
+pub use reconnect::ReconnectPolicy;
+pub use rtsp::{RtspOptions, RtspProtocols, RtspStreamState};
+pub use streamoutput::{StreamOutput, StreamOutputState, StreamTarget};
+pub use syncgroup::SyncGroup;
+pub use video::{ColorBalance, Flip, Rect, Rotation};
+
 pub use gstreamerbase::GstreamerIcedBase;
 
+pub use gstreamerappsrc::{GstreamerIcedAppSrc, PcmFormat};
+
 pub use gstreamerpipewire::GstreamerIcedPipewire;
 
+pub use gstreamercompositor::{
+    CompositorInput, CompositorSource, GstreamerIcedCompositor, InputLayout,
+};
+
+// every instance has its own id, so the subscriptions of them are not merged by iced
+fn next_id() -> usize {
+    static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+// like [gst::Element::static_pad], the missing pad is an error
+fn static_pad(element: &gst::Element, name: &str) -> Result<gst::Pad, IcedGStreamerError> {
+    element
+        .static_pad(name)
+        .ok_or_else(|| IcedGStreamerError::MissingPad {
+            element: element.name().to_string(),
+            pad: name.to_owned(),
+        })
+}
+
+// the caps of the pad, the decoders set them before exposing it, or the ones it can take
+fn pad_caps(pad: &gst::Pad) -> gst::Caps {
+    pad.current_caps().unwrap_or_else(|| pad.query_caps(None))
+}
+
+fn is_video(caps: &gst::CapsRef) -> bool {
+    caps.structure(0)
+        .map(|s| s.name().starts_with("video/"))
+        .unwrap_or(false)
+}
+
+// set a number property of whatever numeric type it has, the elements doing the same thing
+// often differ in it, return false when the element has no such property
+fn set_number_property(element: &gst::Element, name: &str, value: u64) -> bool {
+    let Some(pspec) = element.find_property(name) else {
+        return false;
+    };
+    match pspec.value_type() {
+        glib::Type::U32 => element.set_property(name, value.min(u32::MAX as u64) as u32),
+        glib::Type::U64 => element.set_property(name, value),
+        glib::Type::I32 => element.set_property(name, value.min(i32::MAX as u64) as i32),
+        glib::Type::I64 => element.set_property(name, value.min(i64::MAX as u64) as i64),
+        glib::Type::F32 => element.set_property(name, value as f32),
+        glib::Type::F64 => element.set_property(name, value as f64),
+        _ => return false,
+    }
+    true
+}
+
 #[derive(Debug)]
 pub struct GstreamerIced<const X: usize> {
+    id: usize,
     frame: Arc<Mutex<Option<FrameData>>>, //pipeline: gst::Pipeline,
     bus: gst::Bus,
     source: gst::Bin,
@@ -58,6 +213,23 @@ pub struct GstreamerIced<const X: usize> {
     position: std::time::Duration,
     info_get_started: bool,
     volume: f64,
+    visualization: VisualizationMode,
+    media_checked: bool,
+    audio_only: bool,
+    cover_art: Option<image::Handle>,
+    audio_filter: Option<audio::AudioFilter>,
+    video_filter: video::VideoFilter,
+    rtsp_streams: Vec<RtspStreamState>,
+    is_live: bool,
+    reconnect_policy: Option<ReconnectPolicy>,
+    reconnect: reconnect::ReconnectState,
+    error: Arc<Mutex<Option<IcedGStreamerError>>>,
+    buffering_percent: i32,
+    adaptive: adaptive::AdaptiveStreaming,
+    current_variant: Option<usize>,
+    app_source: Option<gstreamerappsrc::AppSource>,
+    stream_output_state: Option<StreamOutputState>,
+    missing_plugins: Arc<Mutex<Vec<MissingPlugin>>>,
 }
 
 #[derive(Debug, Error)]
@@ -70,8 +242,24 @@ pub enum IcedGStreamerError {
     Bus,
     #[error("{0}")]
     StateChange(#[from] gst::StateChangeError),
-    #[error("failed to cast gstreamer element")]
-    Cast,
+    #[error("{element} is not a {expected}")]
+    WrongType {
+        element: String,
+        expected: &'static str,
+    },
+    #[error("the element {0} is not in the pipeline")]
+    MissingElement(String),
+    #[error("{element} has no pad {pad}")]
+    MissingPad { element: String, pad: String },
+    #[error("failed to link {src} to {sink}: {error}")]
+    Link {
+        src: String,
+        sink: String,
+        #[source]
+        error: gst::PadLinkError,
+    },
+    #[error("failed to set the property {0}")]
+    Property(&'static str),
     #[error("{0}")]
     Io(#[from] std::io::Error),
     #[error("invalid URI")]
@@ -80,8 +268,48 @@ pub enum IcedGStreamerError {
     Caps,
     #[error("failed to query media duration or position")]
     Duration,
-    #[error("failed to sync with playback")]
-    Sync,
+    #[error("a lock is poisoned by a panicking thread")]
+    Poisoned,
+    #[error("the clock is not running")]
+    ClockStopped,
+    #[error("the audio filter is not available")]
+    AudioFilterUnavailable,
+    #[error("invalid equalizer band {0}")]
+    EqualizerBand(usize),
+    #[error("the video filter is not available")]
+    VideoFilterUnavailable,
+    #[error("invalid input {0}")]
+    InvalidInput(usize),
+    #[error("invalid stream variant {0}")]
+    InvalidVariant(usize),
+    #[error("{0}")]
+    Flow(#[from] gst::FlowError),
+    #[error("the frame has {actual} bytes, but {expected} bytes are expected by its size")]
+    InvalidFrame { expected: usize, actual: usize },
+    #[error("the audio source is not available")]
+    AudioSourceUnavailable,
+    #[error("the end of the range is not after its start")]
+    InvalidRange,
+    #[error("invalid crop rect {0:?}, it is empty or outside of the video")]
+    InvalidRect(Rect),
+    #[error("failed to seek to {0}")]
+    SeekFailed(gst::ClockTime),
+    #[error("the {0} queue of the appsrc is full")]
+    QueueFull(&'static str),
+    #[error("no encoder is available for {0:?}")]
+    NoEncoder(VideoCodec),
+    #[error("missing plugin: {}", .0.description)]
+    MissingPlugin(MissingPlugin),
+    /// an error posted on the bus, with the element which failed
+    #[error("{}: {error}", element.as_deref().unwrap_or("pipeline"))]
+    Element {
+        element: Option<String>,
+        factory_name: Option<String>,
+        domain: Option<ErrorDomain>,
+        #[source]
+        error: glib::Error,
+        debug: Option<String>,
+    },
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -121,23 +349,98 @@ pub enum GStreamerMessage {
     FrameUpdate,
     PlayStatusChanged(PlayStatus),
     BusGoToEnd,
+    /// the bus reports an error, it can be got by [GstreamerIced::take_error]
+    BusError,
+    /// the pipeline failed to change its state, the play status is kept, the error can be got
+    /// by [GstreamerIced::take_error]
+    StateChangeFailed,
+    /// the linear volume changed, sent when the volume of playbin is changed
+    VolumeChanged(f64),
+    /// the connection state of a stream of [GstreamerIcedBase::new_rtsp] changed
+    RtspStreamChanged {
+        stream: u32,
+        state: RtspStreamState,
+    },
+    /// the live source is lost, it will be reconnected after the delay of [ReconnectPolicy]
+    Reconnecting {
+        attempt: u32,
+    },
+    /// the delay of [ReconnectPolicy] is over, rebuild the pipeline
+    Reconnect,
+    /// the live source is playing again
+    Reconnected,
+    /// the pipeline reached the playing state
+    PipelinePlaying,
+    /// how much of the buffer is filled, in percent
+    Buffering(i32),
+    /// the variants of the HLS or DASH stream are read from the manifest
+    VariantsFound,
+    /// the HLS or DASH stream switched to the variant of the index
+    VariantSwitched(usize),
+    /// the state of [GstreamerIcedPipewire::start_stream] changed
+    StreamOutputChanged(StreamOutputState),
+    /// a plugin to play the media is not installed, it can be got by
+    /// [GstreamerIced::take_missing_plugins]
+    MissingPlugin,
 }
 
 impl<const X: usize> Drop for GstreamerIced<X> {
     fn drop(&mut self) {
-        self.source
-            .set_state(gst::State::Null)
-            .expect("failed to set state");
+        // nothing can be done about a failure here, the pipeline is freed anyway
+        self.source.set_state(gst::State::Null).ok();
     }
 }
 
 impl<const X: usize> GstreamerIced<X> {
+    // the fields not given here are in the state before anything is played, the backends change
+    // the ones they need
+    fn from_source(
+        source: gst::Bin,
+        frame: Arc<Mutex<Option<FrameData>>>,
+        rv: mpsc::Receiver<GStreamerMessage>,
+        video_filter: video::VideoFilter,
+        play_status: PlayStatus,
+    ) -> Result<Self, IcedGStreamerError> {
+        Ok(Self {
+            id: next_id(),
+            frame,
+            bus: source.bus().ok_or(IcedGStreamerError::Bus)?,
+            source,
+            play_status,
+            rv: Arc::new(AsyncMutex::new(rv)),
+            duration: std::time::Duration::from_nanos(0),
+            position: std::time::Duration::from_nanos(0),
+            info_get_started: true,
+            volume: 0_f64,
+            visualization: VisualizationMode::None,
+            media_checked: true,
+            audio_only: false,
+            cover_art: None,
+            audio_filter: None,
+            video_filter,
+            rtsp_streams: Vec::new(),
+            is_live: false,
+            reconnect_policy: None,
+            reconnect: reconnect::ReconnectState::default(),
+            error: Arc::new(Mutex::new(None)),
+            buffering_percent: 100,
+            adaptive: adaptive::AdaptiveStreaming::default(),
+            current_variant: None,
+            app_source: None,
+            stream_output_state: None,
+            missing_plugins: Arc::new(Mutex::new(Vec::new())),
+        })
+    }
+
     /// return an [image::Handle], you can use it to make image
+    ///
+    /// when the media is audio only and [VisualizationMode::CoverArt] is set, it is the cover art
     pub fn frame_handle(&self) -> Option<image::Handle> {
         self.frame
             .lock()
             .map(|frame| frame.clone().map(|f| f.into()))
             .unwrap_or(None)
+            .or_else(|| self.cover_art.clone())
     }
 
     /// return [FrameData], you can directly access the data
@@ -150,8 +453,59 @@ impl<const X: usize> GstreamerIced<X> {
         &self.play_status
     }
 
+    /// if the media only contains audio, it is known after the media is prerolled
+    pub fn is_audio_only(&self) -> bool {
+        self.audio_only
+    }
+
+    /// the current [VisualizationMode]
+    pub fn visualization(&self) -> VisualizationMode {
+        self.visualization
+    }
+
+    /// take the last error reported by the bus
+    pub fn take_error(&self) -> Option<IcedGStreamerError> {
+        self.error
+            .lock()
+            .map(|mut error| error.take())
+            .unwrap_or(None)
+    }
+
+    fn set_error(&self, err: IcedGStreamerError) {
+        if let Ok(mut error) = self.error.lock() {
+            *error = Some(err);
+        }
+    }
+
+    // the updates cannot return the error, so it is kept like the ones from the bus, the
+    // message only tells it, the pipeline is left as it is
+    fn report_error(&self, err: IcedGStreamerError) -> iced::Command<GStreamerMessage> {
+        self.set_error(err);
+        iced::Command::perform(async {}, |_| GStreamerMessage::StateChangeFailed)
+    }
+
+    /// play or pause, the status is kept when the pipeline fails to change its state, the
+    /// updates do the same for [GStreamerMessage::PlayStatusChanged] and report the failure by
+    /// [GStreamerMessage::StateChangeFailed]
+    pub fn set_play_status(&mut self, status: PlayStatus) -> Result<(), IcedGStreamerError> {
+        match status {
+            PlayStatus::Playing => {
+                self.source.set_state(gst::State::Playing)?;
+            }
+            PlayStatus::Stop => {
+                self.source.set_state(gst::State::Paused)?;
+            }
+            _ => {}
+        }
+        self.play_status = status;
+        Ok(())
+    }
+
     fn is_playing(&self) -> bool {
-        matches!(self.play_status, PlayStatus::Playing)
+        matches!(
+            self.play_status,
+            PlayStatus::Playing | PlayStatus::Buffering
+        )
     }
 
     /// get the subscription, you can use in iced::subscription
@@ -159,12 +513,14 @@ impl<const X: usize> GstreamerIced<X> {
         if self.is_playing() {
             let rv = self.rv.clone();
             let bus = self.bus.clone();
+            let error = self.error.clone();
+            let missing_plugins = self.missing_plugins.clone();
             struct BusWatcher;
             iced::Subscription::batch([
                 iced::time::every(std::time::Duration::from_secs_f64(0.05))
                     .map(|_| GStreamerMessage::Update),
                 iced::subscription::channel(
-                    std::any::TypeId::of::<()>(),
+                    (std::any::TypeId::of::<()>(), self.id),
                     100,
                     |mut output| async move {
                         let mut rv = rv.lock().await;
@@ -177,16 +533,54 @@ impl<const X: usize> GstreamerIced<X> {
                     },
                 ),
                 iced::subscription::channel(
-                    std::any::TypeId::of::<BusWatcher>(),
+                    (std::any::TypeId::of::<BusWatcher>(), self.id),
                     100,
                     |mut output| async move {
                         let mut thebus = bus.stream();
                         while let Some(view) = thebus.next().await {
+                            if let Some(state) = streamoutput::stream_output_state(&view) {
+                                if let gst::MessageView::Error(err) = view.view() {
+                                    if let Ok(mut error) = error.lock() {
+                                        *error = Some(IcedGStreamerError::from_bus_error(err));
+                                    }
+                                }
+                                let _ = output
+                                    .send(GStreamerMessage::StreamOutputChanged(state))
+                                    .await;
+                                continue;
+                            }
+                            if let Some(missing) = MissingPlugin::from_message(&view) {
+                                if let Ok(mut missing_plugins) = missing_plugins.lock() {
+                                    missing_plugins.push(missing);
+                                }
+                                let _ = output.send(GStreamerMessage::MissingPlugin).await;
+                                continue;
+                            }
                             match view.view() {
-                                gst::MessageView::Error(err) => panic!("{:#?}", err),
+                                gst::MessageView::Error(err) => {
+                                    if let Ok(mut error) = error.lock() {
+                                        *error = Some(IcedGStreamerError::from_bus_error(err));
+                                    }
+                                    let _ = output.send(GStreamerMessage::BusError).await;
+                                }
+                                gst::MessageView::Buffering(buffering) => {
+                                    let _ = output
+                                        .send(GStreamerMessage::Buffering(buffering.percent()))
+                                        .await;
+                                }
                                 gst::MessageView::Eos(_eos) => {
                                     let _ = output.send(GStreamerMessage::BusGoToEnd).await;
                                 }
+                                // only the pipeline itself, it has no parent
+                                gst::MessageView::StateChanged(changed)
+                                    if changed.current() == gst::State::Playing
+                                        && view
+                                            .src()
+                                            .map(|src| src.parent().is_none())
+                                            .unwrap_or(false) =>
+                                {
+                                    let _ = output.send(GStreamerMessage::PipelinePlaying).await;
+                                }
                                 _ => {}
                             }
                         }
@@ -210,6 +604,8 @@ impl<const X: usize> GstreamerIced<X> {
         match X {
             0 => "base".to_owned(),
             1 => "pipewire".to_owned(),
+            2 => "compositor".to_owned(),
+            3 => "appsrc".to_owned(),
             _ => unreachable!(),
         }
     }
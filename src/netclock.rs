@@ -0,0 +1,104 @@
+use gst::prelude::*;
+use gstreamer as gst;
+use gstreamer_net as gst_net;
+
+use super::{GstreamerIcedBase, IcedGStreamerError};
+
+/// a clock shared over the network, so players in several processes or machines can play in
+/// sync, one process serves the clock and the others follow it
+///
+/// the base time of the master has to be sent to the slaves by the application, the players
+/// using the clock with the same base time show the same position at the same moment
+#[derive(Debug, Clone)]
+pub struct NetClock {
+    clock: gst::Clock,
+    provider: Option<gst_net::NetTimeProvider>,
+    base_time: gst::ClockTime,
+}
+
+impl NetClock {
+    /// serve the system clock on the address and port, [None] address listens on all
+    /// interfaces and port 0 picks a free port
+    pub fn serve(address: Option<&str>, port: u16) -> Result<Self, IcedGStreamerError> {
+        gst::init()?;
+
+        let clock = gst::SystemClock::obtain();
+        let provider = gst_net::NetTimeProvider::new(&clock, address, port as i32)?;
+        let base_time = clock.time().ok_or(IcedGStreamerError::ClockStopped)?;
+        Ok(Self {
+            clock,
+            provider: Some(provider),
+            base_time,
+        })
+    }
+
+    /// follow the clock served by [NetClock::serve], wait until the clock is synced
+    pub fn follow(
+        address: &str,
+        port: u16,
+        base_time: gst::ClockTime,
+        timeout: std::time::Duration,
+    ) -> Result<Self, IcedGStreamerError> {
+        gst::init()?;
+
+        let clock: gst::Clock =
+            gst_net::NetClientClock::new(None, address, port as i32, gst::ClockTime::ZERO).upcast();
+        clock.wait_for_sync(gst::ClockTime::from_nseconds(timeout.as_nanos() as u64))?;
+        Ok(Self {
+            clock,
+            provider: None,
+            base_time,
+        })
+    }
+
+    /// the clock, it can also be used by [crate::SyncGroup::with_clock]
+    pub fn clock(&self) -> &gst::Clock {
+        &self.clock
+    }
+
+    /// the base time shared by the master and the slaves
+    pub fn base_time(&self) -> gst::ClockTime {
+        self.base_time
+    }
+
+    /// change the base time shared by the master and the slaves, like after a seek, it is
+    /// usually the current time of [NetClock::clock] on the master, sent to the slaves, it takes
+    /// effect by [GstreamerIcedBase::use_net_clock]
+    pub fn set_base_time(&mut self, base_time: gst::ClockTime) {
+        self.base_time = base_time;
+    }
+
+    /// if the clock is served by this process
+    pub fn is_master(&self) -> bool {
+        self.provider.is_some()
+    }
+
+    /// the port the clock is served on, useful when it is served on port 0
+    pub fn port(&self) -> Option<u16> {
+        self.provider
+            .as_ref()
+            .map(|provider| provider.property::<i32>("port") as u16)
+    }
+}
+
+impl GstreamerIcedBase {
+    /// play on the [NetClock] with its base time, it should be called before the media starts
+    /// playing, pause leaves the shared timeline as the base time is not changed after it
+    ///
+    /// a flushing seek, like [GstreamerIcedBase::seek], restarts the running time from zero
+    /// but keeps the base time, so the player skips ahead to catch up with the shared timeline,
+    /// to seek in sync pause every player, seek them to the same position, share a new base
+    /// time by [NetClock::set_base_time] and call it again before playing
+    pub fn use_net_clock(&mut self, net_clock: &NetClock) -> Result<(), IcedGStreamerError> {
+        let pipeline = self.source.downcast_ref::<gst::Pipeline>().ok_or_else(|| {
+            IcedGStreamerError::WrongType {
+                element: self.source.name().to_string(),
+                expected: "pipeline",
+            }
+        })?;
+        pipeline.use_clock(Some(&net_clock.clock));
+        pipeline.set_start_time(gst::ClockTime::NONE);
+        pipeline.set_base_time(net_clock.base_time);
+        Ok(())
+    }
+}
@@ -0,0 +1,268 @@
+use gst::prelude::*;
+use gstreamer as gst;
+use std::path::PathBuf;
+
+use super::plugins::make_element;
+use super::IcedGStreamerError;
+
+/// the horizontal position of a text overlay
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HorizontalAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+/// the vertical position of a text overlay
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VerticalAlign {
+    #[default]
+    Top,
+    Center,
+    Bottom,
+}
+
+/// where the text is drawn on the frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct OverlayPosition {
+    pub halign: HorizontalAlign,
+    pub valign: VerticalAlign,
+    /// the space to the left or right edge in pixels
+    pub xpad: u32,
+    /// the space to the top or bottom edge in pixels
+    pub ypad: u32,
+}
+
+/// how the text looks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FontOptions {
+    /// the font family, like `Sans` or `Monospace`
+    pub family: String,
+    /// the size in points
+    pub size: u32,
+    /// the color in ARGB
+    pub color: u32,
+    /// draw an outline around the text
+    pub outline: bool,
+    /// draw a shaded background behind the text
+    pub shaded_background: bool,
+}
+
+impl Default for FontOptions {
+    fn default() -> Self {
+        Self {
+            family: "Sans".to_owned(),
+            size: 18,
+            color: 0xffffffff,
+            outline: true,
+            shaded_background: false,
+        }
+    }
+}
+
+/// a fixed text, like a watermark
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TextOverlay {
+    pub text: String,
+    pub position: OverlayPosition,
+    pub font: FontOptions,
+}
+
+/// which time is shown by [TimeOverlay]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimeMode {
+    /// the position in the media
+    #[default]
+    StreamTime,
+    /// the time since the pipeline started to play
+    RunningTime,
+    /// the time since the first frame
+    ElapsedRunningTime,
+}
+
+/// a burnt-in timecode
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TimeOverlay {
+    pub mode: TimeMode,
+    pub position: OverlayPosition,
+    pub font: FontOptions,
+}
+
+/// an image, like a logo
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageOverlay {
+    /// the image file, anything gdk-pixbuf can load
+    pub path: PathBuf,
+    /// offset from the left edge in pixels, negative is from the right edge
+    pub x: i32,
+    /// offset from the top edge in pixels, negative is from the bottom edge
+    pub y: i32,
+    /// scale the image to the width, [None] keeps the size of image
+    pub width: Option<u32>,
+    /// scale the image to the height, [None] keeps the size of image
+    pub height: Option<u32>,
+    /// from 0.0 to 1.0
+    pub alpha: f64,
+}
+
+/// the overlays drawn on the video, they are drawn into the frames, so every output of the
+/// pipeline, like [crate::GstreamerIcedPipewire::start_stream] and
+/// [crate::GstreamerIcedAppSrc::new_appsrc_export], gets them, the exports made by
+/// [crate::export_clip] and [crate::export_animation] take them too, [crate::trim_clip] copies
+/// the streams without decoding, so it cannot draw them
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct OverlayConfig {
+    pub text: Option<TextOverlay>,
+    pub time: Option<TimeOverlay>,
+    pub image: Option<ImageOverlay>,
+}
+
+/// textoverlay ! timeoverlay ! gdkpixbufoverlay, all of them are optional
+#[derive(Debug)]
+pub(crate) struct VideoOverlay {
+    text: Option<gst::Element>,
+    time: Option<gst::Element>,
+    image: Option<gst::Element>,
+    config: OverlayConfig,
+}
+
+fn make_silent(factory_name: &str) -> Result<Option<gst::Element>, IcedGStreamerError> {
+    if gst::ElementFactory::find(factory_name).is_none() {
+        return Ok(None);
+    }
+    Ok(Some(
+        make_element(factory_name)?
+            .property("silent", true)
+            .build()?,
+    ))
+}
+
+fn apply_text_style(element: &gst::Element, position: &OverlayPosition, font: &FontOptions) {
+    element.set_property_from_str(
+        "halignment",
+        match position.halign {
+            HorizontalAlign::Left => "left",
+            HorizontalAlign::Center => "center",
+            HorizontalAlign::Right => "right",
+        },
+    );
+    element.set_property_from_str(
+        "valignment",
+        match position.valign {
+            VerticalAlign::Top => "top",
+            VerticalAlign::Center => "center",
+            VerticalAlign::Bottom => "bottom",
+        },
+    );
+    element.set_property("xpad", position.xpad as i32);
+    element.set_property("ypad", position.ypad as i32);
+    element.set_property("font-desc", format!("{} {}", font.family, font.size));
+    element.set_property("color", font.color);
+    element.set_property("draw-outline", font.outline);
+    element.set_property("shaded-background", font.shaded_background);
+}
+
+// the overlays of the config for the export pipelines, nothing when the config is empty
+pub(crate) fn overlay_elements(
+    config: &OverlayConfig,
+) -> Result<Vec<gst::Element>, IcedGStreamerError> {
+    if *config == OverlayConfig::default() {
+        return Ok(Vec::new());
+    }
+    let (mut overlay, elements) = VideoOverlay::new()?;
+    overlay.set_config(config.clone())?;
+    Ok(elements)
+}
+
+impl VideoOverlay {
+    /// build the overlays, return the elements which should be linked in order
+    pub(crate) fn new() -> Result<(Self, Vec<gst::Element>), IcedGStreamerError> {
+        let text = make_silent("textoverlay")?;
+        let time = make_silent("timeoverlay")?;
+        let image = match gst::ElementFactory::find("gdkpixbufoverlay") {
+            Some(_) => Some(
+                make_element("gdkpixbufoverlay")?
+                    .property("alpha", 0.0)
+                    .build()?,
+            ),
+            None => None,
+        };
+        let elements = text
+            .iter()
+            .chain(time.iter())
+            .chain(image.iter())
+            .cloned()
+            .collect();
+        Ok((
+            Self {
+                text,
+                time,
+                image,
+                config: OverlayConfig::default(),
+            },
+            elements,
+        ))
+    }
+
+    pub(crate) fn config(&self) -> &OverlayConfig {
+        &self.config
+    }
+
+    /// apply the config, it can be changed while playing
+    pub(crate) fn set_config(&mut self, config: OverlayConfig) -> Result<(), IcedGStreamerError> {
+        let unavailable = (config.text.is_some() && self.text.is_none())
+            || (config.time.is_some() && self.time.is_none())
+            || (config.image.is_some() && self.image.is_none());
+        if unavailable {
+            return Err(IcedGStreamerError::VideoFilterUnavailable);
+        }
+
+        if let Some(element) = self.text.as_ref() {
+            match config.text.as_ref() {
+                Some(text) => {
+                    apply_text_style(element, &text.position, &text.font);
+                    element.set_property("text", &text.text);
+                    element.set_property("silent", false);
+                }
+                None => element.set_property("silent", true),
+            }
+        }
+
+        if let Some(element) = self.time.as_ref() {
+            match config.time.as_ref() {
+                Some(time) => {
+                    apply_text_style(element, &time.position, &time.font);
+                    element.set_property_from_str(
+                        "time-mode",
+                        match time.mode {
+                            TimeMode::StreamTime => "stream-time",
+                            TimeMode::RunningTime => "running-time",
+                            TimeMode::ElapsedRunningTime => "elapsed-running-time",
+                        },
+                    );
+                    element.set_property("silent", false);
+                }
+                None => element.set_property("silent", true),
+            }
+        }
+
+        if let Some(element) = self.image.as_ref() {
+            match config.image.as_ref() {
+                Some(image) => {
+                    element.set_property("location", image.path.to_string_lossy().as_ref());
+                    element.set_property("offset-x", image.x);
+                    element.set_property("offset-y", image.y);
+                    element.set_property("overlay-width", image.width.unwrap_or(0) as i32);
+                    element.set_property("overlay-height", image.height.unwrap_or(0) as i32);
+                    element.set_property("alpha", image.alpha.clamp(0.0, 1.0));
+                }
+                // gdkpixbufoverlay cannot be disabled, so just make it transparent
+                None => element.set_property("alpha", 0.0),
+            }
+        }
+
+        self.config = config;
+        Ok(())
+    }
+}
@@ -0,0 +1,256 @@
+use gst::glib;
+use gst::glib::translate::{from_glib_full, ToGlibPtr};
+use gst::prelude::*;
+use gstreamer as gst;
+use gstreamer_pbutils as gst_pbutils;
+
+use super::{GstreamerIced, IcedGStreamerError};
+
+// the elements the crate uses, with if it can work without them and what needs them
+const REQUIREMENTS: &[(&str, bool, &str)] = &[
+    ("playbin", true, "playback"),
+    ("uridecodebin", true, "playback"),
+    ("videoconvert", true, "video output"),
+    ("videoscale", true, "video output"),
+    ("appsink", true, "video output"),
+    ("audioconvert", false, "audio filter"),
+    ("rgvolume", false, "replay gain"),
+    ("equalizer-10bands", false, "equalizer"),
+    ("audiopanorama", false, "balance"),
+    ("videocrop", false, "crop"),
+    ("videobalance", false, "color balance"),
+    ("videoflip", false, "rotation and flip"),
+    ("textoverlay", false, "text overlay"),
+    ("timeoverlay", false, "time overlay"),
+    ("gdkpixbufoverlay", false, "image overlay"),
+    ("goom", false, "visualizer"),
+    ("wavescope", false, "visualizer"),
+    ("synaescope", false, "visualizer"),
+    ("souphttpsrc", false, "http"),
+    ("rtspsrc", false, "rtsp"),
+    ("hlsdemux", false, "hls"),
+    ("dashdemux", false, "dash"),
+    ("appsrc", false, "appsrc and in memory media"),
+    ("pipewiresrc", false, "pipewire"),
+    ("compositor", false, "compositor"),
+    ("encodebin", false, "export"),
+    ("filesink", false, "export"),
+    ("urisourcebin", false, "trim"),
+    ("parsebin", false, "trim"),
+    ("gifenc", false, "gif export"),
+    ("webpenc", false, "webp export"),
+    ("h264parse", false, "stream output"),
+    ("flvmux", false, "rtmp output"),
+    ("rtmpsink", false, "rtmp output"),
+    ("mpegtsmux", false, "srt output"),
+    ("srtsink", false, "srt output"),
+    ("rtph264pay", false, "rtp output"),
+    ("udpsink", false, "rtp output"),
+];
+
+/// a plugin which is not installed, from [check_requirements], [IcedGStreamerError::MissingPlugin]
+/// or [GstreamerIced::take_missing_plugins]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingPlugin {
+    /// readable by the users, like `H.264 decoder`
+    pub description: String,
+    /// the string the plugin installers take, like
+    /// `gstreamer|1.0|app|H.264 decoder|decoder-video/x-h264`
+    pub installer_detail: String,
+}
+
+impl MissingPlugin {
+    /// the element of the factory name is missing
+    pub fn element(factory_name: &str) -> Self {
+        pbutils_init();
+        // the same detail as the missing-plugin messages of the element
+        let installer_detail: Option<glib::GString> = unsafe {
+            from_glib_full(gst_pbutils::ffi::gst_missing_element_installer_detail_new(
+                factory_name.to_glib_none().0,
+            ))
+        };
+        Self {
+            description: gst_pbutils::pb_utils_get_element_description(factory_name).into(),
+            installer_detail: installer_detail.map(Into::into).unwrap_or_default(),
+        }
+    }
+
+    // the message is posted by playbin and decodebin when nothing can handle a stream, the
+    // detail is made by gstreamer, it strips the fields of the caps the installers do not match
+    pub(crate) fn from_message(message: &gst::MessageRef) -> Option<Self> {
+        pbutils_init();
+        let message = message.as_ptr() as *mut gst::ffi::GstMessage;
+        unsafe {
+            if gst_pbutils::ffi::gst_is_missing_plugin_message(message) == glib::ffi::GFALSE {
+                return None;
+            }
+            let description: Option<glib::GString> = from_glib_full(
+                gst_pbutils::ffi::gst_missing_plugin_message_get_description(message),
+            );
+            let installer_detail: Option<glib::GString> = from_glib_full(
+                gst_pbutils::ffi::gst_missing_plugin_message_get_installer_detail(message),
+            );
+            Some(Self {
+                description: description?.into(),
+                installer_detail: installer_detail?.into(),
+            })
+        }
+    }
+}
+
+// the functions of pbutils not wrapped by gstreamer-pbutils need it, it does nothing after the
+// first call
+fn pbutils_init() {
+    unsafe { gst_pbutils::ffi::gst_pb_utils_init() };
+}
+
+/// if an element the crate uses is installed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PluginStatus {
+    pub factory_name: &'static str,
+    /// the crate does not work without it, the others only disable a feature
+    pub required: bool,
+    /// the feature needs it
+    pub used_for: &'static str,
+    pub available: bool,
+}
+
+impl PluginStatus {
+    /// the [MissingPlugin] to install it
+    pub fn missing_plugin(&self) -> MissingPlugin {
+        MissingPlugin::element(self.factory_name)
+    }
+}
+
+/// check the elements the crate uses, call it at the startup to tell the users what is missing
+pub fn check_requirements() -> Result<Vec<PluginStatus>, IcedGStreamerError> {
+    gst::init()?;
+
+    Ok(REQUIREMENTS
+        .iter()
+        .map(|(factory_name, required, used_for)| PluginStatus {
+            factory_name,
+            required: *required,
+            used_for,
+            available: gst::ElementFactory::find(factory_name).is_some(),
+        })
+        .collect())
+}
+
+/// run the plugin installer of the system, like the one of PackageKit, with the plugins, it is
+/// `gst-install-plugins-helper` or the one in `GST_INSTALL_PLUGINS_HELPER`, reload the
+/// registry by [gst::Registry::update] after it exits successfully
+// it runs the same helper as gst_install_plugins_async, which reports the end by the default
+// glib main context, but iced does not run it
+pub fn install_missing_plugins(
+    plugins: &[MissingPlugin],
+) -> Result<std::process::Child, IcedGStreamerError> {
+    let helper = std::env::var_os("GST_INSTALL_PLUGINS_HELPER")
+        .unwrap_or_else(|| "gst-install-plugins-helper".into());
+    Ok(std::process::Command::new(helper)
+        .args(plugins.iter().map(|plugin| &plugin.installer_detail))
+        .spawn()?)
+}
+
+/// like [gst::ElementFactory::make], but a missing element is reported as
+/// [IcedGStreamerError::MissingPlugin]
+pub(crate) fn make_element(
+    factory_name: &str,
+) -> Result<gst::element_factory::ElementBuilder<'_>, IcedGStreamerError> {
+    if gst::ElementFactory::find(factory_name).is_none() {
+        return Err(IcedGStreamerError::MissingPlugin(MissingPlugin::element(
+            factory_name,
+        )));
+    }
+    Ok(gst::ElementFactory::make(factory_name))
+}
+
+// the callbacks cannot return the error, so the missing element is posted on the bus by the
+// element making it, like the missing-plugin messages of decodebin
+pub(crate) fn make_element_or_post<'a>(
+    poster: &gst::Element,
+    factory_name: &'a str,
+) -> Option<gst::element_factory::ElementBuilder<'a>> {
+    match make_element(factory_name) {
+        Ok(builder) => Some(builder),
+        Err(_) => {
+            pbutils_init();
+            let message: Option<gst::Message> = unsafe {
+                from_glib_full(gst_pbutils::ffi::gst_missing_element_message_new(
+                    poster.to_glib_none().0,
+                    factory_name.to_glib_none().0,
+                ))
+            };
+            if let Some(message) = message {
+                poster.post_message(message).ok();
+            }
+            None
+        }
+    }
+}
+
+// the streams nothing takes are linked to fakesink, or the not-linked error stops the element
+// giving them, it is called from pad-added, so the missing fakesink is posted by the element
+pub(crate) fn link_to_fakesink(pipeline: &gst::Pipeline, element: &gst::Element, pad: &gst::Pad) {
+    // the pipeline is already running, so do not wait for the preroll of it
+    let Some(Ok(fakesink)) = make_element_or_post(element, "fakesink")
+        .map(|builder| builder.property("async", false).build())
+    else {
+        return;
+    };
+    if pipeline.add(&fakesink).is_err() {
+        return;
+    }
+    fakesink.sync_state_with_parent().ok();
+    if let Some(sinkpad) = fakesink.static_pad("sink") {
+        pad.link(&sinkpad).ok();
+    }
+}
+
+impl<const X: usize> GstreamerIced<X> {
+    /// take the plugins reported missing by the bus, they come with
+    /// [crate::GStreamerMessage::MissingPlugin]
+    pub fn take_missing_plugins(&self) -> Vec<MissingPlugin> {
+        self.missing_plugins
+            .lock()
+            .map(|mut missing| std::mem::take(&mut *missing))
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detail_from_the_caps_of_the_message() {
+        gst::init().unwrap();
+        let caps = gst::Caps::builder("video/x-h264")
+            .field("stream-format", "avc")
+            .field("width", 1920i32)
+            .field("height", 1080i32)
+            .field("framerate", gst::Fraction::new(30, 1))
+            .field("codec_data", gst::Buffer::from_slice([1u8, 2, 3]))
+            .build();
+        let s = gst::Structure::builder("missing-plugin")
+            .field("type", "decoder")
+            .field("detail", caps)
+            .field("name", "H.264 decoder")
+            .build();
+        let message = gst::message::Element::new(s);
+
+        let missing = MissingPlugin::from_message(&message).unwrap();
+        assert_eq!(missing.description, "H.264 decoder");
+        assert!(missing.installer_detail.starts_with("gstreamer|1.0|"));
+        assert!(missing
+            .installer_detail
+            .ends_with("|H.264 decoder|decoder-video/x-h264, stream-format=(string)avc"));
+    }
+
+    #[test]
+    fn other_messages_are_not_missing_plugins() {
+        gst::init().unwrap();
+        let message = gst::message::Element::new(gst::Structure::new_empty("other"));
+        assert_eq!(MissingPlugin::from_message(&message), None);
+    }
+}
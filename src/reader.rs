@@ -0,0 +1,85 @@
+use gst::prelude::*;
+use gstreamer as gst;
+use gstreamer_app as gst_app;
+use std::io::{Read, Seek, SeekFrom};
+use std::sync::{Arc, Mutex};
+
+use super::{GstreamerIcedBase, IcedGStreamerError};
+
+// appsrc asks for u32::MAX when it does not care about the size
+const MAX_CHUNK_SIZE: usize = 64 * 1024;
+
+impl GstreamerIcedBase {
+    /// play the media in the memory, like `Vec<u8>` or `bytes::Bytes`, it never touches the
+    /// filesystem
+    pub fn new_bytes<B>(bytes: B) -> Result<Self, IcedGStreamerError>
+    where
+        B: AsRef<[u8]> + Send + 'static,
+    {
+        Self::new_reader(std::io::Cursor::new(bytes))
+    }
+
+    /// play the media read from the reader, like the media decrypted in rust, seeking is done
+    /// by seeking the reader
+    pub fn new_reader<R>(mut reader: R) -> Result<Self, IcedGStreamerError>
+    where
+        R: Read + Seek + Send + 'static,
+    {
+        let size = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(0))?;
+        let reader = Arc::new(Mutex::new(reader));
+
+        Self::new_playbin("appsrc://", false, move |source, _| {
+            let Some(appsrc) = source.downcast_ref::<gst_app::AppSrc>() else {
+                return;
+            };
+            // the source is set up again after the pipeline goes to null, so read from the start
+            if let Ok(mut reader) = reader.lock() {
+                reader.seek(SeekFrom::Start(0)).ok();
+            }
+            appsrc.set_stream_type(gst_app::AppStreamType::RandomAccess);
+            appsrc.set_size(size as i64);
+            appsrc.set_format(gst::Format::Bytes);
+
+            let need_reader = Arc::clone(&reader);
+            let seek_reader = Arc::clone(&reader);
+            appsrc.set_callbacks(
+                gst_app::AppSrcCallbacks::builder()
+                    .need_data(move |appsrc, length| {
+                        let Ok(mut reader) = need_reader.lock() else {
+                            gst::element_error!(
+                                appsrc,
+                                gst::ResourceError::Read,
+                                ["the reader is poisoned"]
+                            );
+                            return;
+                        };
+                        let mut data = vec![0; (length as usize).clamp(1, MAX_CHUNK_SIZE)];
+                        match reader.read(&mut data) {
+                            Ok(0) => {
+                                let _ = appsrc.end_of_stream();
+                            }
+                            Ok(read) => {
+                                data.truncate(read);
+                                let _ = appsrc.push_buffer(gst::Buffer::from_mut_slice(data));
+                            }
+                            Err(err) => {
+                                gst::element_error!(
+                                    appsrc,
+                                    gst::ResourceError::Read,
+                                    ["failed to read the media: {}", err]
+                                );
+                            }
+                        }
+                    })
+                    .seek_data(move |_, offset| {
+                        seek_reader
+                            .lock()
+                            .map(|mut reader| reader.seek(SeekFrom::Start(offset)).is_ok())
+                            .unwrap_or(false)
+                    })
+                    .build(),
+            );
+        })
+    }
+}
@@ -0,0 +1,215 @@
+use gst::prelude::*;
+use gstreamer as gst;
+use iced::Command;
+
+use super::{GStreamerMessage, GstreamerIcedBase, PlayStatus};
+
+/// how a live source is reconnected after it fails or ends
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+    /// give up after so many attempts in a row
+    pub max_retries: u32,
+    /// the delay before the first attempt, it is doubled for every following attempt
+    pub initial_delay: std::time::Duration,
+    /// the delay never grows over it
+    pub max_delay: std::time::Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            initial_delay: std::time::Duration::from_secs(1),
+            max_delay: std::time::Duration::from_secs(30),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// the delay before the given attempt, the first attempt is 1
+    pub fn delay(&self, attempt: u32) -> std::time::Duration {
+        self.initial_delay
+            .saturating_mul(2_u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay)
+    }
+}
+
+// what to do when the stream is lost
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StreamLost {
+    /// schedule the attempt
+    Schedule(u32),
+    /// the retries are used up
+    GiveUp,
+    /// a reconnect is already scheduled, the failure posts several errors on the bus
+    Ignore,
+}
+
+// the reconnect attempts in a row, apart from the player so it is tested without a pipeline
+#[derive(Debug, Default)]
+pub(crate) struct ReconnectState {
+    attempt: u32,
+    // set from scheduling the reconnect until it runs
+    pending: bool,
+}
+
+impl ReconnectState {
+    fn lost(&mut self, policy: &ReconnectPolicy) -> StreamLost {
+        if self.pending {
+            return StreamLost::Ignore;
+        }
+        if self.attempt >= policy.max_retries {
+            self.attempt = 0;
+            return StreamLost::GiveUp;
+        }
+        self.attempt += 1;
+        self.pending = true;
+        StreamLost::Schedule(self.attempt)
+    }
+
+    // the scheduled reconnect runs, false if there is none
+    fn start(&mut self) -> bool {
+        std::mem::replace(&mut self.pending, false)
+    }
+
+    // false if it was not reconnecting
+    fn recovered(&mut self) -> bool {
+        self.pending = false;
+        std::mem::replace(&mut self.attempt, 0) != 0
+    }
+}
+
+impl GstreamerIcedBase {
+    /// reconnect the live source when it fails or ends, [None] disables it, it only works when
+    /// the player is created as live
+    pub fn set_reconnect_policy(&mut self, policy: Option<ReconnectPolicy>) {
+        self.reconnect_policy = policy;
+    }
+
+    /// if the source is being reconnected
+    pub fn is_reconnecting(&self) -> bool {
+        self.reconnect.attempt != 0
+    }
+
+    // called when the bus reports an error or the end, schedule the next reconnect if possible
+    pub(crate) fn on_stream_lost(&mut self) -> Command<GStreamerMessage> {
+        let Some(policy) = self.reconnect_policy.filter(|_| self.is_live) else {
+            self.play_status = PlayStatus::End;
+            return Command::none();
+        };
+        let attempt = match self.reconnect.lost(&policy) {
+            StreamLost::Schedule(attempt) => attempt,
+            StreamLost::GiveUp => {
+                self.play_status = PlayStatus::End;
+                return Command::none();
+            }
+            StreamLost::Ignore => return Command::none(),
+        };
+        let delay = policy.delay(attempt);
+        Command::batch([
+            Command::perform(async move { attempt }, |attempt| {
+                GStreamerMessage::Reconnecting { attempt }
+            }),
+            Command::perform(futures_time::task::sleep(delay.into()), |_| {
+                GStreamerMessage::Reconnect
+            }),
+        ])
+    }
+
+    // tear down and rebuild the pipeline, the last frame is kept until a new one comes
+    pub(crate) fn reconnect(&mut self) -> Command<GStreamerMessage> {
+        if !self.reconnect.start() {
+            return Command::none();
+        }
+        let rebuilt = self
+            .source
+            .set_state(gst::State::Null)
+            .and_then(|_| self.source.set_state(gst::State::Playing));
+        if let Err(err) = rebuilt {
+            self.set_error(err.into());
+            return self.on_stream_lost();
+        }
+        Command::none()
+    }
+
+    // the pipeline is playing or the buffer is full after the reconnect, so it is done, the
+    // audio only streams have no frame to tell it
+    pub(crate) fn on_recovered(&mut self) -> Command<GStreamerMessage> {
+        if !self.reconnect.recovered() {
+            return Command::none();
+        }
+        Command::perform(async {}, |_| GStreamerMessage::Reconnected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ReconnectPolicy, ReconnectState, StreamLost};
+    use std::time::Duration;
+
+    #[test]
+    fn delay_doubles_from_the_initial_delay() {
+        let policy = ReconnectPolicy {
+            max_retries: 10,
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+        };
+        assert_eq!(policy.delay(1), Duration::from_secs(1));
+        assert_eq!(policy.delay(2), Duration::from_secs(2));
+        assert_eq!(policy.delay(3), Duration::from_secs(4));
+        assert_eq!(policy.delay(5), Duration::from_secs(16));
+    }
+
+    #[test]
+    fn delay_is_capped_by_max_delay() {
+        let policy = ReconnectPolicy::default();
+        assert_eq!(policy.delay(6), Duration::from_secs(30));
+        assert_eq!(policy.delay(u32::MAX), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn attempt_zero_is_the_initial_delay() {
+        let policy = ReconnectPolicy::default();
+        assert_eq!(policy.delay(0), policy.initial_delay);
+    }
+
+    #[test]
+    fn errors_in_a_row_are_one_attempt() {
+        let policy = ReconnectPolicy::default();
+        let mut state = ReconnectState::default();
+        // the source error and the internal data stream error from downstream
+        assert_eq!(state.lost(&policy), StreamLost::Schedule(1));
+        assert_eq!(state.lost(&policy), StreamLost::Ignore);
+        assert_eq!(state.attempt, 1);
+        // the reconnect fails again
+        assert!(state.start());
+        assert_eq!(state.lost(&policy), StreamLost::Schedule(2));
+    }
+
+    #[test]
+    fn recovered_resets_the_attempts() {
+        let policy = ReconnectPolicy::default();
+        let mut state = ReconnectState::default();
+        assert_eq!(state.lost(&policy), StreamLost::Schedule(1));
+        assert!(state.start());
+        assert!(state.recovered());
+        assert!(!state.recovered());
+        assert!(!state.start());
+        assert_eq!(state.lost(&policy), StreamLost::Schedule(1));
+    }
+
+    #[test]
+    fn gives_up_after_max_retries() {
+        let policy = ReconnectPolicy {
+            max_retries: 2,
+            ..Default::default()
+        };
+        let mut state = ReconnectState::default();
+        assert_eq!(state.lost(&policy), StreamLost::Schedule(1));
+        assert!(state.start());
+        assert_eq!(state.lost(&policy), StreamLost::Schedule(2));
+        assert!(state.start());
+        assert_eq!(state.lost(&policy), StreamLost::GiveUp);
+        assert_eq!(state.attempt, 0);
+    }
+}
@@ -0,0 +1,155 @@
+use gst::prelude::*;
+use gstreamer as gst;
+
+use super::{GStreamerMessage, GstreamerIcedBase, IcedGStreamerError};
+
+/// the transports rtspsrc may try, in the order of udp, multicast udp and tcp
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RtspProtocols {
+    pub udp: bool,
+    pub udp_multicast: bool,
+    pub tcp: bool,
+}
+
+impl Default for RtspProtocols {
+    fn default() -> Self {
+        Self {
+            udp: true,
+            udp_multicast: true,
+            tcp: true,
+        }
+    }
+}
+
+impl RtspProtocols {
+    fn to_flags(self) -> String {
+        [
+            (self.udp, "udp"),
+            (self.udp_multicast, "udp-mcast"),
+            (self.tcp, "tcp"),
+        ]
+        .into_iter()
+        .filter_map(|(enabled, nick)| enabled.then_some(nick))
+        .collect::<Vec<_>>()
+        .join("+")
+    }
+}
+
+/// the settings of rtspsrc
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RtspOptions {
+    /// how much data is buffered to handle the network jitter
+    pub latency: std::time::Duration,
+    pub protocols: RtspProtocols,
+    pub user_id: Option<String>,
+    pub user_password: Option<String>,
+    /// how many times to retry when allocating the rtp ports
+    pub retry: u32,
+    /// switch to tcp when no udp data is received in the time
+    pub timeout: std::time::Duration,
+    /// fail when the tcp connection gets no response in the time
+    pub tcp_timeout: std::time::Duration,
+}
+
+impl Default for RtspOptions {
+    fn default() -> Self {
+        Self {
+            latency: std::time::Duration::from_millis(2000),
+            protocols: RtspProtocols::default(),
+            user_id: None,
+            user_password: None,
+            retry: 20,
+            timeout: std::time::Duration::from_secs(5),
+            tcp_timeout: std::time::Duration::from_secs(20),
+        }
+    }
+}
+
+/// the connection state of a stream of the rtsp source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RtspStreamState {
+    /// the stream is described by the server and being set up
+    Connecting,
+    /// the data of the stream is coming
+    Streaming,
+    /// the stream ended or failed
+    Disconnected,
+}
+
+// the pads of rtspsrc are called recv_rtp_src_{stream}_{ssrc}_{pt}
+fn stream_of_pad(pad: &gst::Pad) -> Option<u32> {
+    pad.name()
+        .strip_prefix("recv_rtp_src_")?
+        .split('_')
+        .next()?
+        .parse()
+        .ok()
+}
+
+impl GstreamerIcedBase {
+    /// accept a rtsp url, like from a security camera, the connection state of every stream is
+    /// sent by [GStreamerMessage::RtspStreamChanged]
+    pub fn new_rtsp(url: &url::Url, options: RtspOptions) -> Result<Self, IcedGStreamerError> {
+        Self::new_playbin(url.as_str(), true, move |source, sd| {
+            if source
+                .factory()
+                .map(|factory| factory.name() != "rtspsrc")
+                .unwrap_or(true)
+            {
+                return;
+            }
+            source.set_property("latency", options.latency.as_millis() as u32);
+            source.set_property_from_str("protocols", &options.protocols.to_flags());
+            if let Some(user_id) = options.user_id.as_ref() {
+                source.set_property("user-id", user_id);
+            }
+            if let Some(user_password) = options.user_password.as_ref() {
+                source.set_property("user-pw", user_password);
+            }
+            source.set_property("retry", options.retry);
+            source.set_property("timeout", options.timeout.as_micros() as u64);
+            source.set_property("tcp-timeout", options.tcp_timeout.as_micros() as u64);
+
+            let select_sd = sd.clone();
+            source.connect("select-stream", false, move |args| {
+                if let Some(Ok(stream)) = args.get(1).map(|arg| arg.get::<u32>()) {
+                    select_sd
+                        .clone()
+                        .try_send(GStreamerMessage::RtspStreamChanged {
+                            stream,
+                            state: RtspStreamState::Connecting,
+                        })
+                        .ok();
+                }
+                Some(true.to_value())
+            });
+
+            let pad_sd = sd.clone();
+            source.connect_pad_added(move |_, pad| {
+                if let Some(stream) = stream_of_pad(pad) {
+                    pad_sd
+                        .clone()
+                        .try_send(GStreamerMessage::RtspStreamChanged {
+                            stream,
+                            state: RtspStreamState::Streaming,
+                        })
+                        .ok();
+                }
+            });
+        })
+    }
+
+    /// the connection state of the streams of the rtsp source, the index is the stream number
+    pub fn rtsp_streams(&self) -> &[RtspStreamState] {
+        &self.rtsp_streams
+    }
+
+    pub(crate) fn update_rtsp_stream(&mut self, stream: u32, state: RtspStreamState) {
+        let stream = stream as usize;
+        if self.rtsp_streams.len() <= stream {
+            self.rtsp_streams
+                .resize(stream + 1, RtspStreamState::Connecting);
+        }
+        self.rtsp_streams[stream] = state;
+    }
+}
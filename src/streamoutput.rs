@@ -0,0 +1,235 @@
+use gst::prelude::*;
+use gstreamer as gst;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use super::encoder::{make_encoder, EncoderSettings, VideoCodec};
+use super::plugins::make_element;
+use super::{static_pad, GstreamerIcedPipewire, IcedGStreamerError};
+
+// the names the elements are found by in the pipeline
+pub(crate) const OUTPUT_TEE_NAME: &str = "output_tee";
+const STREAM_OUTPUT_NAME: &str = "stream_output";
+const STREAMING_MESSAGE: &str = "stream-output-streaming";
+
+/// where [GstreamerIcedPipewire::start_stream] sends the video
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamTarget {
+    /// flv to a rtmp url, like `rtmp://live.example.com/app/key`
+    Rtmp(String),
+    /// mpeg-ts to a srt uri, like `srt://127.0.0.1:8888`
+    Srt(String),
+    /// h264 in rtp to a udp address, it can be received on the same machine with
+    /// `udpsrc port=5000 ! application/x-rtp,encoding-name=H264 ! rtph264depay ! ...`
+    RtpUdp { host: String, port: u16 },
+}
+
+/// the settings of the stream sent by [GstreamerIcedPipewire::start_stream]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamOutput {
+    pub target: StreamTarget,
+    /// kbit per second
+    pub bitrate: u32,
+    /// the max frames between the keyframes, the viewers joining later wait for one
+    pub keyframe_interval: u32,
+}
+
+impl StreamOutput {
+    pub fn new(target: StreamTarget) -> Self {
+        Self {
+            target,
+            bitrate: 4000,
+            keyframe_interval: 60,
+        }
+    }
+}
+
+/// the state of the stream sent by [GstreamerIcedPipewire::start_stream]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamOutputState {
+    /// the output is set up, no data is sent yet
+    Connecting,
+    /// the data is being sent
+    Streaming,
+    /// the output failed, the error can be got by [crate::GstreamerIced::take_error], the
+    /// capture and the preview keep going, the output is dropped until
+    /// [GstreamerIcedPipewire::stop_stream] or another [GstreamerIcedPipewire::start_stream]
+    Failed,
+    Stopped,
+}
+
+// queue ! videoconvert ! the best h264 encoder ! h264parse ! the muxer and the sink of the target
+fn stream_branch(output: &StreamOutput) -> Result<gst::Bin, IcedGStreamerError> {
+    let bin = gst::Bin::with_name(STREAM_OUTPUT_NAME);
+
+    // drop the frames when the network is slow, rather than stall the preview
+    let queue = make_element("queue")?
+        .property("max-size-buffers", 30u32)
+        .property("max-size-bytes", 0u32)
+        .property("max-size-time", 0u64)
+        .property_from_str("leaky", "downstream")
+        .build()?;
+    let (_, encoder) = make_encoder(
+        VideoCodec::H264,
+        EncoderSettings {
+            bitrate: output.bitrate,
+            keyframe_interval: output.keyframe_interval,
+            low_latency: true,
+            ..Default::default()
+        },
+    )?;
+    let mut elements = vec![
+        queue,
+        make_element("videoconvert")?.build()?,
+        encoder,
+        make_element("h264parse")?.build()?,
+    ];
+    match &output.target {
+        StreamTarget::Rtmp(location) => elements.extend([
+            make_element("flvmux")?
+                .property("streamable", true)
+                .build()?,
+            make_element("rtmpsink")?
+                .property("location", location)
+                .build()?,
+        ]),
+        StreamTarget::Srt(uri) => elements.extend([
+            make_element("mpegtsmux")?.build()?,
+            make_element("srtsink")?.property("uri", uri).build()?,
+        ]),
+        StreamTarget::RtpUdp { host, port } => elements.extend([
+            make_element("rtph264pay")?
+                .property("config-interval", -1i32)
+                .build()?,
+            make_element("udpsink")?
+                .property("host", host)
+                .property("port", *port as i32)
+                .build()?,
+        ]),
+    }
+    bin.add_many(&elements)?;
+    gst::Element::link_many(&elements)?;
+
+    let sinkpad = static_pad(&elements[0], "sink")?;
+    // the errors of the output are not returned to the tee, or it stops the capture too, they
+    // are posted on the bus by the failing element, and the output only drops the data after
+    let failed = Arc::new(AtomicBool::new(false));
+    let list_failed = Arc::clone(&failed);
+    let ghost = gst::GhostPad::builder_with_target(&sinkpad)?
+        .chain_function(move |pad, parent, buffer| {
+            if !failed.load(Ordering::Relaxed)
+                && gst::ProxyPad::chain_default(pad, parent, buffer).is_err()
+            {
+                failed.store(true, Ordering::Relaxed);
+            }
+            Ok(gst::FlowSuccess::Ok)
+        })
+        .chain_list_function(move |pad, parent, list| {
+            if !list_failed.load(Ordering::Relaxed)
+                && gst::ProxyPad::chain_list_default(pad, parent, list).is_err()
+            {
+                list_failed.store(true, Ordering::Relaxed);
+            }
+            Ok(gst::FlowSuccess::Ok)
+        })
+        .build();
+    ghost.set_active(true)?;
+    bin.add_pad(&ghost)?;
+
+    // the first buffer reaching the sink means the connection is made
+    if let Some(sink) = elements.last() {
+        if let Some(pad) = sink.static_pad("sink") {
+            let sink = sink.clone();
+            pad.add_probe(gst::PadProbeType::BUFFER, move |_, _| {
+                sink.post_message(
+                    gst::message::Application::builder(gst::Structure::new_empty(
+                        STREAMING_MESSAGE,
+                    ))
+                    .src(&sink)
+                    .build(),
+                )
+                .ok();
+                gst::PadProbeReturn::Remove
+            });
+        }
+    }
+    Ok(bin)
+}
+
+// the messages of the stream output are turned into its state, so an error of it does not end
+// the capture
+pub(crate) fn stream_output_state(message: &gst::MessageRef) -> Option<StreamOutputState> {
+    let from_output = message
+        .src()
+        .map(|src| {
+            std::iter::successors(Some(src.clone()), |object| object.parent())
+                .any(|object| object.name() == STREAM_OUTPUT_NAME)
+        })
+        .unwrap_or(false);
+    if !from_output {
+        return None;
+    }
+    match message.view() {
+        gst::MessageView::Error(_) => Some(StreamOutputState::Failed),
+        gst::MessageView::Application(app)
+            if app
+                .structure()
+                .map(|s| s.name() == STREAMING_MESSAGE)
+                .unwrap_or(false) =>
+        {
+            Some(StreamOutputState::Streaming)
+        }
+        _ => None,
+    }
+}
+
+impl GstreamerIcedPipewire {
+    /// send the capture to the [StreamTarget] beside the preview, the overlays and the filters
+    /// are included, the state is sent by [crate::GStreamerMessage::StreamOutputChanged]
+    pub fn start_stream(&mut self, output: StreamOutput) -> Result<(), IcedGStreamerError> {
+        self.stop_stream()?;
+
+        let tee = self
+            .source
+            .by_name(OUTPUT_TEE_NAME)
+            .ok_or_else(|| IcedGStreamerError::MissingElement(OUTPUT_TEE_NAME.to_owned()))?;
+        let bin = stream_branch(&output)?;
+        self.source.add(&bin)?;
+        let teepad =
+            tee.request_pad_simple("src_%u")
+                .ok_or_else(|| IcedGStreamerError::MissingPad {
+                    element: OUTPUT_TEE_NAME.to_owned(),
+                    pad: "src_%u".to_owned(),
+                })?;
+        let sinkpad = static_pad(bin.upcast_ref(), "sink")?;
+        teepad
+            .link(&sinkpad)
+            .map_err(|err| IcedGStreamerError::link(&teepad, &sinkpad, err))?;
+        bin.sync_state_with_parent()?;
+
+        self.stream_output_state = Some(StreamOutputState::Connecting);
+        Ok(())
+    }
+
+    /// stop sending the capture, the preview keeps going
+    pub fn stop_stream(&mut self) -> Result<(), IcedGStreamerError> {
+        let Some(bin) = self.source.by_name(STREAM_OUTPUT_NAME) else {
+            return Ok(());
+        };
+        if let Some(teepad) = bin.static_pad("sink").and_then(|pad| pad.peer()) {
+            if let Some(tee) = teepad.parent_element() {
+                tee.release_request_pad(&teepad);
+            }
+        }
+        bin.set_state(gst::State::Null)?;
+        self.source.remove(&bin)?;
+
+        self.stream_output_state = Some(StreamOutputState::Stopped);
+        Ok(())
+    }
+
+    /// the state of the stream output, [None] if it is never started
+    pub fn stream_output_state(&self) -> Option<StreamOutputState> {
+        self.stream_output_state
+    }
+}
@@ -0,0 +1,189 @@
+use gst::prelude::*;
+use gst::GenericFormattedValue;
+use gstreamer as gst;
+
+use super::{GStreamerMessage, GstreamerIcedBase, IcedGStreamerError, PlayStatus, Position};
+
+// the time given to every pipeline to start playing at the same base time
+const START_DELAY: gst::ClockTime = gst::ClockTime::from_mseconds(100);
+
+/// several [GstreamerIcedBase] playing on one clock, like a video wall, all of them are played,
+/// paused and seeked together
+#[derive(Debug)]
+pub struct SyncGroup {
+    players: Vec<GstreamerIcedBase>,
+    clock: gst::Clock,
+}
+
+impl SyncGroup {
+    /// put the players on the system clock
+    pub fn new(players: Vec<GstreamerIcedBase>) -> Result<Self, IcedGStreamerError> {
+        gst::init()?;
+        Self::with_clock(players, gst::SystemClock::obtain())
+    }
+
+    /// put the players on the given clock, like a network clock
+    pub fn with_clock(
+        players: Vec<GstreamerIcedBase>,
+        clock: gst::Clock,
+    ) -> Result<Self, IcedGStreamerError> {
+        for player in players.iter() {
+            let pipeline = player
+                .source
+                .downcast_ref::<gst::Pipeline>()
+                .ok_or_else(|| IcedGStreamerError::WrongType {
+                    element: player.source.name().to_string(),
+                    expected: "pipeline",
+                })?;
+            pipeline.use_clock(Some(&clock));
+            // the base time is set by the group, so keep gstreamer from changing it
+            pipeline.set_start_time(gst::ClockTime::NONE);
+        }
+        Ok(Self { players, clock })
+    }
+
+    /// the players of the group
+    pub fn players(&self) -> &[GstreamerIcedBase] {
+        &self.players
+    }
+
+    /// the players of the group, change the play status of one of them breaks the sync, use
+    /// the methods of the group instead
+    pub fn players_mut(&mut self) -> &mut [GstreamerIcedBase] {
+        &mut self.players
+    }
+
+    /// the clock shared by the players
+    pub fn clock(&self) -> &gst::Clock {
+        &self.clock
+    }
+
+    /// play all the players from the position of the first one
+    pub fn play(&mut self) -> Result<(), IcedGStreamerError> {
+        let position = self
+            .players
+            .first()
+            .and_then(|player| player.source.query_position::<gst::ClockTime>())
+            .unwrap_or(gst::ClockTime::ZERO);
+        self.pause_and_seek(Position::Time(position.into()))?;
+        self.start()
+    }
+
+    /// pause all the players
+    pub fn pause(&mut self) -> Result<(), IcedGStreamerError> {
+        for player in self.players.iter_mut() {
+            player.source.set_state(gst::State::Paused)?;
+            player.play_status = PlayStatus::Stop;
+        }
+        Ok(())
+    }
+
+    /// seek all the players, they keep playing if they were playing
+    pub fn seek<T>(&mut self, position: T) -> Result<(), IcedGStreamerError>
+    where
+        T: Into<Position>,
+    {
+        let was_playing = self.players.iter().any(|player| player.is_playing());
+        self.pause_and_seek(position.into())?;
+        if was_playing {
+            self.start()?;
+        }
+        Ok(())
+    }
+
+    /// how far every player is from the first one in seconds, positive is ahead
+    pub fn drift(&self) -> Vec<f64> {
+        let positions: Vec<Option<gst::ClockTime>> = self
+            .players
+            .iter()
+            .map(|player| player.source.query_position::<gst::ClockTime>())
+            .collect();
+        let Some(Some(reference)) = positions.first().copied() else {
+            return vec![0.0; positions.len()];
+        };
+        positions
+            .into_iter()
+            .map(|position| {
+                position
+                    .map(|position| {
+                        (position.nseconds() as f64 - reference.nseconds() as f64) / 1e9
+                    })
+                    .unwrap_or(0.0)
+            })
+            .collect()
+    }
+
+    /// get the subscription, the message comes with the index of the player
+    pub fn subscription(&self) -> iced::Subscription<(usize, GStreamerMessage)> {
+        iced::Subscription::batch(
+            self.players
+                .iter()
+                .enumerate()
+                .map(|(index, player)| player.subscription().with(index)),
+        )
+    }
+
+    /// update the player of the index, the play status changes are applied to the whole group
+    pub fn update(
+        &mut self,
+        (index, message): (usize, GStreamerMessage),
+    ) -> iced::Command<(usize, GStreamerMessage)> {
+        match message {
+            GStreamerMessage::PlayStatusChanged(PlayStatus::Playing) => {
+                let result = self.play();
+                self.report_result(index, result)
+            }
+            GStreamerMessage::PlayStatusChanged(PlayStatus::Stop) => {
+                let result = self.pause();
+                self.report_result(index, result)
+            }
+            message => match self.players.get_mut(index) {
+                Some(player) => player.update(message).map(move |message| (index, message)),
+                None => iced::Command::none(),
+            },
+        }
+    }
+
+    // the failure is kept by the player the message is for, like the updates of the players do
+    fn report_result(
+        &self,
+        index: usize,
+        result: Result<(), IcedGStreamerError>,
+    ) -> iced::Command<(usize, GStreamerMessage)> {
+        match (result, self.players.get(index)) {
+            (Err(err), Some(player)) => player
+                .report_error(err)
+                .map(move |message| (index, message)),
+            _ => iced::Command::none(),
+        }
+    }
+
+    fn pause_and_seek(&mut self, position: Position) -> Result<(), IcedGStreamerError> {
+        self.pause()?;
+        for player in self.players.iter() {
+            player.source.state(gst::ClockTime::from_seconds(5)).0?;
+        }
+        let position: GenericFormattedValue = position.into();
+        for player in self.players.iter() {
+            player
+                .source
+                .seek_simple(gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE, position)?;
+        }
+        for player in self.players.iter() {
+            player.source.state(gst::ClockTime::from_seconds(5)).0?;
+        }
+        Ok(())
+    }
+
+    // all the players are prerolled at the same position, give them the same base time so the
+    // first frame is shown at the same moment
+    fn start(&mut self) -> Result<(), IcedGStreamerError> {
+        let base_time = self.clock.time().ok_or(IcedGStreamerError::ClockStopped)? + START_DELAY;
+        for player in self.players.iter_mut() {
+            player.source.set_base_time(base_time);
+            player.source.set_state(gst::State::Playing)?;
+            player.play_status = PlayStatus::Playing;
+        }
+        Ok(())
+    }
+}
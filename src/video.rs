@@ -0,0 +1,356 @@
+use futures::channel::mpsc;
+use gst::prelude::*;
+use gstreamer as gst;
+use gstreamer_app as gst_app;
+use std::sync::{Arc, Mutex};
+
+use super::overlay::{OverlayConfig, VideoOverlay};
+use super::plugins::make_element;
+use super::{static_pad, FrameData, GStreamerMessage, GstreamerIced, IcedGStreamerError};
+
+/// the color balance of the video, the default keeps the video untouched
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorBalance {
+    /// from -1.0 to 1.0
+    pub brightness: f64,
+    /// from 0.0 to 2.0
+    pub contrast: f64,
+    /// from 0.0 to 2.0
+    pub saturation: f64,
+    /// from -1.0 to 1.0
+    pub hue: f64,
+}
+
+impl Default for ColorBalance {
+    fn default() -> Self {
+        Self {
+            brightness: 0.0,
+            contrast: 1.0,
+            saturation: 1.0,
+            hue: 0.0,
+        }
+    }
+}
+
+/// rotate the video
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Rotation {
+    #[default]
+    None,
+    /// rotate 90 degrees clockwise
+    Cw90,
+    Rotate180,
+    /// rotate 90 degrees counterclockwise
+    Ccw90,
+}
+
+/// flip the video, it is applied after [Rotation]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Flip {
+    #[default]
+    None,
+    Horizontal,
+    Vertical,
+}
+
+/// a rectangle in the pixels of the source video
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// the video filters between videoconvert and videoscale: videocrop ! videobalance ! videoflip
+/// ! overlays
+///
+/// every filter is optional, it is [None] when the plugin is not installed
+#[derive(Debug)]
+pub(crate) struct VideoFilter {
+    crop: Option<gst::Element>,
+    crop_rect: Arc<Mutex<Option<Rect>>>,
+    balance: Option<gst::Element>,
+    flip: Option<gst::Element>,
+    rotation: Rotation,
+    flip_mode: Flip,
+    auto_orientation: bool,
+    overlay: VideoOverlay,
+}
+
+impl VideoFilter {
+    /// build the filters, return the elements which should be linked in order
+    pub(crate) fn new() -> Result<(Self, Vec<gst::Element>), IcedGStreamerError> {
+        let crop_rect: Arc<Mutex<Option<Rect>>> = Arc::new(Mutex::new(None));
+        let crop = match gst::ElementFactory::find("videocrop") {
+            Some(_) => {
+                let crop = make_element("videocrop")?.build()?;
+                let rect_ref = Arc::clone(&crop_rect);
+                // the crop is described by the borders, so it need to be updated with the size
+                static_pad(&crop, "sink")?.connect_notify(Some("caps"), move |pad, _| {
+                    let (Some(crop), Ok(rect)) = (pad.parent_element(), rect_ref.lock()) else {
+                        return;
+                    };
+                    apply_crop(&crop, *rect);
+                });
+                Some(crop)
+            }
+            None => None,
+        };
+        let balance = match gst::ElementFactory::find("videobalance") {
+            Some(_) => Some(make_element("videobalance")?.build()?),
+            None => None,
+        };
+        let flip = match gst::ElementFactory::find("videoflip") {
+            Some(_) => Some(
+                make_element("videoflip")?
+                    .property_from_str("video-direction", "auto")
+                    .build()?,
+            ),
+            None => None,
+        };
+        let (overlay, overlay_elements) = VideoOverlay::new()?;
+        let elements = crop
+            .iter()
+            .chain(balance.iter())
+            .chain(flip.iter())
+            .cloned()
+            .chain(overlay_elements)
+            .collect();
+        Ok((
+            Self {
+                crop,
+                crop_rect,
+                balance,
+                flip,
+                rotation: Rotation::None,
+                flip_mode: Flip::None,
+                auto_orientation: true,
+                overlay,
+            },
+            elements,
+        ))
+    }
+
+    fn video_direction(&self) -> &'static str {
+        match (self.rotation, self.flip_mode) {
+            (Rotation::None, Flip::None) if self.auto_orientation => "auto",
+            (Rotation::None, Flip::None) => "identity",
+            (Rotation::None, Flip::Horizontal) => "horiz",
+            (Rotation::None, Flip::Vertical) => "vert",
+            (Rotation::Cw90, Flip::None) => "90r",
+            (Rotation::Cw90, Flip::Horizontal) => "ul-lr",
+            (Rotation::Cw90, Flip::Vertical) => "ur-ll",
+            (Rotation::Rotate180, Flip::None) => "180",
+            (Rotation::Rotate180, Flip::Horizontal) => "vert",
+            (Rotation::Rotate180, Flip::Vertical) => "horiz",
+            (Rotation::Ccw90, Flip::None) => "90l",
+            (Rotation::Ccw90, Flip::Horizontal) => "ur-ll",
+            (Rotation::Ccw90, Flip::Vertical) => "ul-lr",
+        }
+    }
+
+    fn update_flip(&self) -> Result<(), IcedGStreamerError> {
+        let flip = self
+            .flip
+            .as_ref()
+            .ok_or(IcedGStreamerError::VideoFilterUnavailable)?;
+        flip.set_property_from_str("video-direction", self.video_direction());
+        Ok(())
+    }
+}
+
+/// videoconvert ! filters ! videoscale ! appsink, return the elements which should be linked in
+/// order, the frames from appsink are stored in `frame`
+pub(crate) fn video_output(
+    frame: &Arc<Mutex<Option<FrameData>>>,
+    mut sd: mpsc::Sender<GStreamerMessage>,
+) -> Result<(VideoFilter, Vec<gst::Element>), IcedGStreamerError> {
+    let videoconvert = make_element("videoconvert")?.build()?;
+    let videoscale = make_element("videoscale")?.build()?;
+
+    let app_sink_caps = gst::Caps::builder("video/x-raw")
+        .field("format", "RGBA")
+        .field("pixel-aspect-ratio", gst::Fraction::new(1, 1))
+        .build();
+
+    let app_sink: gst_app::AppSink = gst_app::AppSink::builder()
+        .name("app_sink")
+        .caps(&app_sink_caps)
+        .build();
+
+    let frame_ref = Arc::clone(frame);
+
+    app_sink.set_callbacks(
+        gst_app::AppSinkCallbacks::builder()
+            .new_sample(move |sink| {
+                let sample = sink.pull_sample().map_err(|_| gst::FlowError::Eos)?;
+                let buffer = sample.buffer().ok_or(gst::FlowError::Error)?;
+                let map = buffer.map_readable().map_err(|_| gst::FlowError::Error)?;
+
+                let caps = sample.caps().ok_or(gst::FlowError::Error)?;
+                let s = caps.structure(0).ok_or(gst::FlowError::Error)?;
+                let width = s.get::<i32>("width").map_err(|_| gst::FlowError::Error)?;
+                let height = s.get::<i32>("height").map_err(|_| gst::FlowError::Error)?;
+                *frame_ref.lock().map_err(|_| gst::FlowError::Error)? = Some(FrameData {
+                    width: width as _,
+                    height: height as _,
+                    pixels: map.as_slice().to_owned(),
+                });
+                sd.try_send(GStreamerMessage::FrameUpdate).ok();
+                Ok(gst::FlowSuccess::Ok)
+            })
+            .build(),
+    );
+
+    let (video_filter, filter_elements) = VideoFilter::new()?;
+    let mut elements = vec![videoconvert];
+    elements.extend(filter_elements);
+    elements.extend([videoscale, app_sink.into()]);
+    Ok((video_filter, elements))
+}
+
+// the size of the source video, [None] before the caps are negotiated
+fn frame_size(crop: &gst::Element) -> Option<(u32, u32)> {
+    let caps = crop.static_pad("sink")?.current_caps()?;
+    let s = caps.structure(0)?;
+    let width = s.get::<i32>("width").ok()?;
+    let height = s.get::<i32>("height").ok()?;
+    Some((width as u32, height as u32))
+}
+
+fn apply_crop(crop: &gst::Element, rect: Option<Rect>) {
+    let Some((width, height)) = frame_size(crop) else {
+        return;
+    };
+    // a rect set before the size was known may be outside of the video, it would leave nothing
+    // of it, so the whole video is shown instead
+    let (left, top, right, bottom) = match rect {
+        Some(rect) if rect.x < width && rect.y < height => (
+            rect.x,
+            rect.y,
+            width.saturating_sub(rect.x.saturating_add(rect.width)),
+            height.saturating_sub(rect.y.saturating_add(rect.height)),
+        ),
+        _ => (0, 0, 0, 0),
+    };
+    crop.set_property("left", left as i32);
+    crop.set_property("top", top as i32);
+    crop.set_property("right", right as i32);
+    crop.set_property("bottom", bottom as i32);
+}
+
+impl<const X: usize> GstreamerIced<X> {
+    /// only output the given region of the source video, it can be changed while playing, the
+    /// empty rect and the rect starting outside of the video are rejected, the part of the rect
+    /// outside of the video is left out
+    pub fn set_crop(&mut self, rect: Rect) -> Result<(), IcedGStreamerError> {
+        if rect.width == 0 || rect.height == 0 {
+            return Err(IcedGStreamerError::InvalidRect(rect));
+        }
+        // the size is only known once the video is playing, before it the rect is kept and
+        // clamped to the video later
+        let size = self.video_filter.crop.as_ref().and_then(frame_size);
+        if let Some((width, height)) = size {
+            if rect.x >= width || rect.y >= height {
+                return Err(IcedGStreamerError::InvalidRect(rect));
+            }
+        }
+        self.update_crop(Some(rect))
+    }
+
+    /// output the whole video again
+    pub fn clear_crop(&mut self) -> Result<(), IcedGStreamerError> {
+        self.update_crop(None)
+    }
+
+    /// the current crop region
+    pub fn crop(&self) -> Option<Rect> {
+        self.video_filter
+            .crop_rect
+            .lock()
+            .map(|rect| *rect)
+            .unwrap_or(None)
+    }
+
+    fn update_crop(&mut self, rect: Option<Rect>) -> Result<(), IcedGStreamerError> {
+        let crop = self
+            .video_filter
+            .crop
+            .as_ref()
+            .ok_or(IcedGStreamerError::VideoFilterUnavailable)?;
+        *self
+            .video_filter
+            .crop_rect
+            .lock()
+            .map_err(|_| IcedGStreamerError::Poisoned)? = rect;
+        apply_crop(crop, rect);
+        Ok(())
+    }
+
+    /// set the brightness, contrast, saturation and hue, it can be changed while playing
+    pub fn set_color_balance(&mut self, balance: ColorBalance) -> Result<(), IcedGStreamerError> {
+        let videobalance = self
+            .video_filter
+            .balance
+            .as_ref()
+            .ok_or(IcedGStreamerError::VideoFilterUnavailable)?;
+        videobalance.set_property("brightness", balance.brightness.clamp(-1.0, 1.0));
+        videobalance.set_property("contrast", balance.contrast.clamp(0.0, 2.0));
+        videobalance.set_property("saturation", balance.saturation.clamp(0.0, 2.0));
+        videobalance.set_property("hue", balance.hue.clamp(-1.0, 1.0));
+        Ok(())
+    }
+
+    /// get the current [ColorBalance]
+    pub fn color_balance(&self) -> ColorBalance {
+        let Some(videobalance) = self.video_filter.balance.as_ref() else {
+            return ColorBalance::default();
+        };
+        ColorBalance {
+            brightness: videobalance.property("brightness"),
+            contrast: videobalance.property("contrast"),
+            saturation: videobalance.property("saturation"),
+            hue: videobalance.property("hue"),
+        }
+    }
+
+    /// rotate the video, it can be changed while playing
+    pub fn set_rotation(&mut self, rotation: Rotation) -> Result<(), IcedGStreamerError> {
+        self.video_filter.rotation = rotation;
+        self.video_filter.update_flip()
+    }
+
+    /// the current [Rotation]
+    pub fn rotation(&self) -> Rotation {
+        self.video_filter.rotation
+    }
+
+    /// flip the video, it can be changed while playing
+    pub fn set_flip(&mut self, flip: Flip) -> Result<(), IcedGStreamerError> {
+        self.video_filter.flip_mode = flip;
+        self.video_filter.update_flip()
+    }
+
+    /// the current [Flip]
+    pub fn flip(&self) -> Flip {
+        self.video_filter.flip_mode
+    }
+
+    /// follow the `image-orientation` tag, like the videos taken by phones, it is enabled by
+    /// default and only works when [Rotation::None] and [Flip::None] are set
+    pub fn set_auto_orientation(&mut self, enabled: bool) -> Result<(), IcedGStreamerError> {
+        self.video_filter.auto_orientation = enabled;
+        self.video_filter.update_flip()
+    }
+
+    /// draw text, timecode or image on the video, it can be changed while playing
+    pub fn set_overlay(&mut self, config: OverlayConfig) -> Result<(), IcedGStreamerError> {
+        self.video_filter.overlay.set_config(config)
+    }
+
+    /// the current [OverlayConfig]
+    pub fn overlay(&self) -> &OverlayConfig {
+        self.video_filter.overlay.config()
+    }
+}